@@ -1,9 +1,9 @@
 #!/usr/bin/env python3
 """
-Pictaflux Diffusion Sidecar — WebSocket server wrapping
+Pictaflux Diffusion Sidecar — WebSocket and HTTP server wrapping
 streamdiffusion-mac's Pipeline for real-time img2img.
 
-Protocol:
+WebSocket protocol (GET / with an upgrade):
   - Binary messages: JPEG image data (client -> server -> client)
   - Text messages: JSON commands
     - {"type": "set_prompt", "prompt": "..."}
@@ -11,11 +11,28 @@ Protocol:
     - {"type": "set_strength", "value": 0.5}
     - {"type": "set_lerp_speed", "value": 0.05}
     - {"type": "set_seed", "value": 42}
+    - {"type": "set_seed_lock", "value": false}
     - {"type": "set_cfg_scale", "value": 7.5}
     - {"type": "set_negative_prompt", "prompt": "..."}
     - {"type": "set_num_steps", "value": 4}
+    - {"type": "set_min_frame_interval", "value": 250}, in ms; 0 disables
     - {"type": "ping"} -> {"type": "pong"}
 
+HTTP control API, on the same port. Every response carries
+X-Pictaflux-Api with API_VERSION; a request naming another version is
+refused with 400.
+  - GET  /health      -> {"status": "ok", "model": "..."}
+  - GET  /models      -> [{"id", "name", "path", "size_mb"}, ...]
+  - POST /model       {"model": "..."}, reloads the pipeline
+  - POST /model_cache {"path": "..."}, used by later model loads
+  - POST /flush_cache -> {"freed_mb"}, null without a torch GPU backend
+  - POST /params      {"prompt"?, "feedback"?, "strength"?, "feedback_mode"?,
+                       "seed_locked"?}, applied live
+  - POST /process     {"image_base64", "compressed", "prompt", "seed",
+                       "regions": [{"mask_base64", "prompt"}]}
+                      -> {"image_base64"}, a PNG (zlib-compressed when
+                      "compressed" is set) the size of the input
+
 Lifecycle:
   - Prints "LOADING" to stdout when starting model load
   - Prints "READY:<port>" to stdout when WebSocket server is listening
@@ -23,11 +40,15 @@ Lifecycle:
   - Shuts down on SIGTERM, SIGINT, or all clients disconnect
 """
 import asyncio
+import base64
+import gc
 import signal
 import sys
 import os
+import time
 import json
 import argparse
+import zlib
 import numpy as np
 import cv2
 
@@ -40,10 +61,29 @@ os.environ["HF_HUB_OFFLINE"] = "1"
 sys.path.insert(0, os.path.join(os.path.dirname(os.path.abspath(__file__)), "streamdiffusion-mac"))
 from camera import Pipeline, COREML_DIR
 
-import websockets
+from aiohttp import web, WSMsgType
+
 
+# Version of the HTTP control API; must match the app's API_VERSION.
+API_VERSION = "1"
+API_HEADER = "X-Pictaflux-Api"
+
+# Weight file extensions listed by GET /models, as in the app's models.rs.
+WEIGHT_EXTENSIONS = (".safetensors", ".ckpt", ".pt", ".bin", ".mlpackage", ".mlmodelc")
 
 pipeline = None
+_args = None
+_model_name = None
+
+# Held while a frame renders or the pipeline is swapped. Renders run in an
+# executor thread so the event loop stays free for health checks and
+# commands, and this keeps two of them from sharing the pipeline's buffers.
+_render_lock = None
+
+# Prompt embeddings by prompt, so per-frame and per-region prompts are only
+# encoded once.
+_embed_cache = {}
+_EMBED_CACHE_SIZE = 32
 
 # Noise schedule for SDEdit-style img2img strength control.
 # Computed once after pipeline init; used by set_strength().
@@ -56,6 +96,20 @@ _cfg_scale = 1.0
 _num_steps = 1
 _negative_embeds = None  # Encoded from "" at startup; updated via set_negative_prompt
 
+# Low-power pacing: frames are rendered at least this many seconds apart.
+_min_frame_interval = 0.0
+_last_frame_time = 0.0
+
+# Locked, every frame reuses the noise for _seed; unlocked, the seed
+# advances by one per frame.
+_seed_locked = True
+_seed = 42
+
+# How the previous frame's latent is blended into the next one.
+FEEDBACK_MODES = ("alpha", "additive", "difference", "momentum")
+_feedback_mode = "alpha"
+_feedback_ema = None  # Running average of past latents, for "momentum"
+
 
 def _compute_alphas_cumprod():
     """Compute the standard stable diffusion noise schedule.
@@ -88,6 +142,35 @@ def set_strength(strength):
     pipeline._t_buf[0] = np.float16(t)
 
 
+def emit_metric(name, value, unit=None, parts=None):
+    """Report a measurement to the Rust host as a METRIC line, with an
+    optional breakdown as (name, value) pairs in the same unit.
+
+    Goes through sys.stdout, which points at stderr once READY is sent; the
+    host parses METRIC lines from both streams. Each line becomes an IPC
+    event, so send one per frame rather than one per step.
+    """
+    metric = {"name": name, "value": value, "unit": unit}
+    if parts:
+        metric["parts"] = [{"name": n, "value": v} for n, v in parts]
+    print("METRIC:" + json.dumps(metric), flush=True)
+
+
+def blend_feedback(clean, prev, fb):
+    """Mix the previous frame's latent into the current one per _feedback_mode."""
+    global _feedback_ema
+    if _feedback_mode == "additive":
+        return clean + fb * prev
+    if _feedback_mode == "difference":
+        # Push away from the last frame, exaggerating what changed.
+        return clean + fb * (clean - prev)
+    if _feedback_mode == "momentum":
+        # Blend toward an average of recent frames rather than only the last.
+        _feedback_ema = prev if _feedback_ema is None else 0.5 * _feedback_ema + 0.5 * prev
+        return (1.0 - fb) * clean + fb * _feedback_ema
+    return (1.0 - fb) * clean + fb * prev
+
+
 def process_frame_advanced(frame_bgr):
     """Process a frame with optional CFG and multi-step denoising.
 
@@ -95,10 +178,12 @@ def process_frame_advanced(frame_bgr):
     Advanced path: reimplements the processing loop with CFG double-pass and
     DDIM multi-step denoising using the pipeline's CoreML models directly.
     """
-    if _cfg_scale <= 1.0 and _num_steps <= 1:
+    if _cfg_scale <= 1.0 and _num_steps <= 1 and _feedback_mode == "alpha":
         return pipeline.process_frame(frame_bgr)
 
     # --- Advanced path ---
+    frame_start = time.perf_counter()
+    timings = []
 
     # 1. Preprocess: crop to square + resize + normalize
     h, w = frame_bgr.shape[:2]
@@ -126,8 +211,7 @@ def process_frame_advanced(frame_bgr):
 
     # 4. Latent feedback from previous frame
     if pipeline._prev_denoised is not None and pipeline.latent_feedback > 0:
-        fb = np.float16(pipeline.latent_feedback)
-        clean = (1.0 - fb) * clean + fb * pipeline._prev_denoised
+        clean = blend_feedback(clean, pipeline._prev_denoised, np.float16(pipeline.latent_feedback))
 
     # 5. Add noise at start timestep
     t_start = int(_current_strength * _max_timestep)
@@ -151,7 +235,8 @@ def process_frame_advanced(frame_bgr):
     neg_embeds = _negative_embeds
     pos_embeds = pipeline._prompt_embeds
 
-    for t, t_next in zip(timesteps, next_ts):
+    for step, (t, t_next) in enumerate(zip(timesteps, next_ts)):
+        step_start = time.perf_counter()
         t_buf[0] = np.float16(t)
 
         # UNet forward pass (conditional)
@@ -180,75 +265,166 @@ def process_frame_advanced(frame_bgr):
 
         pred_x0 = (x - sqrt_1mat * npred) / sqrt_at
         x = sqrt_an * pred_x0 + sqrt_1man * npred
+        timings.append((f"step_{step}", (time.perf_counter() - step_start) * 1000))
 
     # 8. Store for latent feedback
     pipeline._prev_denoised = x.copy()
 
     # 9. VAE Decode
+    decode_start = time.perf_counter()
     dec = pipeline.vae_decoder.predict({"latent": x})
+    timings.append(("vae_decode", (time.perf_counter() - decode_start) * 1000))
     r = np.array(dec["image"]).astype(np.float32).squeeze(0).transpose(1, 2, 0)
     r = ((r + 1.0) * 127.5).clip(0, 255).astype(np.uint8)
     if r.shape[0] != pipeline.output_size:
         r = cv2.resize(r, (pipeline.output_size, pipeline.output_size))
+    emit_metric("frame", (time.perf_counter() - frame_start) * 1000, "ms", timings)
     return cv2.cvtColor(r, cv2.COLOR_RGB2BGR)
 
 
-async def handle_client(websocket):
-    """Handle a single WebSocket client connection."""
-    global pipeline
-    if pipeline is None:
-        await websocket.close(1011, "Pipeline not initialized")
+def reseed(seed):
+    """Regenerate the pipeline's fixed noise from `seed`."""
+    global _seed
+    _seed = seed
+    if pipeline is not None:
+        # RandomState only takes 32-bit seeds; the app sends 64-bit ones.
+        pipeline._fixed_noise = np.random.RandomState(seed % 2**32).randn(
+            *pipeline._fixed_noise.shape
+        ).astype(pipeline._fixed_noise.dtype)
+
+
+def encode_prompt(prompt):
+    """Prompt embeddings for `prompt`, from the cache when possible."""
+    embeds = _embed_cache.get(prompt)
+    if embeds is None:
+        if len(_embed_cache) >= _EMBED_CACHE_SIZE:
+            _embed_cache.clear()
+        embeds = _embed_cache[prompt] = pipeline._encode_single(prompt)
+    return embeds
+
+
+def apply_prompt(prompt):
+    """Make `prompt` the session prompt; frames blend toward it."""
+    if not prompt or pipeline is None or prompt == getattr(pipeline, "_current_prompt", None):
         return
+    pipeline._target_embeds = encode_prompt(prompt)
+    pipeline._current_prompt = prompt
 
+
+def render_with_embeds(frame_bgr, embeds):
+    """Render one frame with `embeds` in place of the session prompt,
+    leaving the prompt and the feedback latent as they were."""
+    saved = (pipeline._prompt_embeds, pipeline._target_embeds, pipeline._prev_denoised)
+    pipeline._prompt_embeds = pipeline._target_embeds = embeds
     try:
-        async for message in websocket:
-            if isinstance(message, bytes):
-                # Binary: JPEG image data
-                arr = np.frombuffer(message, dtype=np.uint8)
-                frame_bgr = cv2.imdecode(arr, cv2.IMREAD_COLOR)
-                if frame_bgr is None:
-                    continue
-                result_bgr = process_frame_advanced(frame_bgr)
-                _, jpeg = cv2.imencode(
-                    ".jpg", result_bgr, [cv2.IMWRITE_JPEG_QUALITY, 90]
+        return process_frame_advanced(frame_bgr)
+    finally:
+        pipeline._prompt_embeds, pipeline._target_embeds, pipeline._prev_denoised = saved
+
+
+def render_at_input_size(frame_bgr, render):
+    """Run `render` on `frame_bgr` and return a result the same size.
+
+    The pipeline renders square frames, cropping anything else to its
+    center, so the frame is padded out to a square first and the padding
+    cropped off the result.
+    """
+    h, w = frame_bgr.shape[:2]
+    side = max(h, w)
+    padded = cv2.copyMakeBorder(frame_bgr, 0, side - h, 0, side - w, cv2.BORDER_REPLICATE)
+    result = cv2.resize(render(padded), (side, side))
+    return result[:h, :w]
+
+
+def warm_up(width, height):
+    """Render one blank frame at the app's canvas size, so the first real
+    frame doesn't pay for CoreML's first-run compilation or the padding and
+    resize buffers for that size. The session prompt and feedback latent
+    are left as they were."""
+    frame = np.zeros((height, width, 3), dtype=np.uint8)
+    embeds = pipeline._prompt_embeds
+    render_at_input_size(frame, lambda f: render_with_embeds(f, embeds))
+
+
+def decode_image(data, flags):
+    image = cv2.imdecode(np.frombuffer(data, dtype=np.uint8), flags)
+    if image is None:
+        raise ValueError("not a decodable image")
+    return image
+
+
+async def handle_client(request):
+    """Handle a single WebSocket client connection."""
+    ws = web.WebSocketResponse()
+    await ws.prepare(request)
+    if pipeline is None:
+        await ws.close(code=1011, message=b"Pipeline not initialized")
+        return ws
+
+    async for msg in ws:
+        if msg.type == WSMsgType.BINARY:
+            # Binary: JPEG image data
+            arr = np.frombuffer(msg.data, dtype=np.uint8)
+            frame_bgr = cv2.imdecode(arr, cv2.IMREAD_COLOR)
+            if frame_bgr is None:
+                continue
+            await pace_frame()
+            async with _render_lock:
+                if not _seed_locked:
+                    reseed(_seed + 1)
+                result_bgr = await asyncio.get_running_loop().run_in_executor(
+                    None, process_frame_advanced, frame_bgr
                 )
-                await websocket.send(jpeg.tobytes())
-            elif isinstance(message, str):
-                # Text: JSON command
-                try:
-                    cmd = json.loads(message)
-                    await handle_command(websocket, cmd)
-                except json.JSONDecodeError:
-                    pass
-    except websockets.exceptions.ConnectionClosed:
-        pass
+            _, jpeg = cv2.imencode(
+                ".jpg", result_bgr, [cv2.IMWRITE_JPEG_QUALITY, 90]
+            )
+            await ws.send_bytes(jpeg.tobytes())
+        elif msg.type == WSMsgType.TEXT:
+            # Text: JSON command
+            try:
+                cmd = json.loads(msg.data)
+                await handle_command(ws, cmd)
+            except json.JSONDecodeError:
+                pass
+    return ws
+
+
+async def pace_frame():
+    """Wait out the rest of the low-power frame interval, if any."""
+    global _last_frame_time
+    wait = _min_frame_interval - (time.monotonic() - _last_frame_time)
+    if wait > 0:
+        await asyncio.sleep(wait)
+    _last_frame_time = time.monotonic()
 
 
 async def handle_command(ws, cmd):
     """Handle a JSON command from the client."""
-    global pipeline, _cfg_scale, _num_steps, _negative_embeds
+    global pipeline, _cfg_scale, _num_steps, _negative_embeds, _feedback_mode, _feedback_ema
+    global _seed_locked, _min_frame_interval
     t = cmd.get("type")
     if t == "set_prompt":
         prompt = cmd.get("prompt", "")
         if prompt and pipeline is not None:
-            new_embed = pipeline._encode_single(prompt)
-            pipeline._target_embeds = new_embed
-            pipeline._current_prompt = prompt
-            await ws.send(json.dumps({"type": "prompt_set", "prompt": prompt}))
+            apply_prompt(prompt)
+            await ws.send_str(json.dumps({"type": "prompt_set", "prompt": prompt}))
     elif t == "set_feedback":
         if pipeline is not None:
             pipeline.latent_feedback = float(cmd.get("value", 0.1))
+    elif t == "set_feedback_mode":
+        mode = cmd.get("value", "alpha")
+        if mode in FEEDBACK_MODES:
+            _feedback_mode = mode
+            _feedback_ema = None
     elif t == "set_strength":
         set_strength(float(cmd.get("value", 0.5)))
     elif t == "set_lerp_speed":
         if pipeline is not None:
             pipeline._prompt_lerp_speed = float(cmd.get("value", 0.05))
     elif t == "set_seed":
-        if pipeline is not None:
-            seed = int(cmd.get("value", 42))
-            pipeline._fixed_noise = np.random.RandomState(seed).randn(
-                *pipeline._fixed_noise.shape
-            ).astype(pipeline._fixed_noise.dtype)
+        reseed(int(cmd.get("value", 42)))
+    elif t == "set_seed_lock":
+        _seed_locked = bool(cmd.get("value", True))
     elif t == "set_cfg_scale":
         _cfg_scale = max(1.0, float(cmd.get("value", 1.0)))
     elif t == "set_negative_prompt":
@@ -257,33 +433,272 @@ async def handle_command(ws, cmd):
             _negative_embeds = pipeline._encode_single(prompt)
     elif t == "set_num_steps":
         _num_steps = max(1, min(8, int(cmd.get("value", 1))))
+    elif t == "set_min_frame_interval":
+        _min_frame_interval = max(0.0, float(cmd.get("value", 0))) / 1000
     elif t == "ping":
-        await ws.send(json.dumps({"type": "pong"}))
+        await ws.send_str(json.dumps({"type": "pong"}))
+
+
+@web.middleware
+async def api_version(request, handler):
+    """Stamp every HTTP response with the API version, and refuse requests
+    written for another one."""
+    client = request.headers.get(API_HEADER)
+    if client is not None and client != API_VERSION:
+        return web.json_response(
+            {"error": f"this sidecar speaks control API version {API_VERSION}"},
+            status=400,
+            headers={API_HEADER: API_VERSION},
+        )
+    try:
+        response = await handler(request)
+    except web.HTTPException as e:
+        e.headers[API_HEADER] = API_VERSION
+        raise
+    if not response.prepared:
+        response.headers[API_HEADER] = API_VERSION
+    return response
+
+
+def bad_request(message):
+    return web.json_response({"error": message}, status=400)
+
+
+async def http_health(request):
+    return web.json_response({"status": "ok", "model": _model_name})
+
+
+async def http_models(request):
+    models_dir = os.path.join(os.path.dirname(os.path.abspath(__file__)), "models")
+    models = []
+    if os.path.isdir(models_dir):
+        for entry in sorted(os.scandir(models_dir), key=lambda e: e.name):
+            model_id, ext = os.path.splitext(entry.name)
+            if ext not in WEIGHT_EXTENSIONS:
+                continue
+            models.append({
+                "id": model_id,
+                "name": model_id.replace("_", " ").replace("-", " "),
+                "path": entry.path,
+                "size_mb": disk_size(entry.path) / (1024 * 1024),
+            })
+    if _model_name and not any(m["id"] == _model_name for m in models):
+        models.insert(0, {"id": _model_name, "name": _model_name, "path": "", "size_mb": 0.0})
+    return web.json_response(models)
 
 
-async def main_async(args):
-    global pipeline, _alphas_cumprod, _max_timestep, _negative_embeds
+def disk_size(path):
+    if not os.path.isdir(path):
+        return os.path.getsize(path)
+    return sum(disk_size(entry.path) for entry in os.scandir(path))
 
-    print("LOADING", flush=True)
 
-    # Load pipeline (blocking — runs in executor to not block the event loop)
-    loop = asyncio.get_event_loop()
+async def http_model(request):
+    """Load another model, keeping the current one until it is ready."""
+    global pipeline, _model_name, _negative_embeds
+    body = await request.json()
+    model = body.get("model")
+    if not model:
+        return bad_request("model is required")
+    loop = asyncio.get_running_loop()
+    try:
+        loaded = await loop.run_in_executor(None, lambda: load_pipeline(_args, model))
+    except Exception as e:
+        return web.json_response({"error": f"failed to load {model}: {e}"}, status=500)
+    async with _render_lock:
+        previous, pipeline = pipeline, loaded
+        _model_name = model
+        _embed_cache.clear()
+        # Carry the live prompt and feedback over rather than the startup ones.
+        pipeline.latent_feedback = previous.latent_feedback
+        prompt = getattr(previous, "_current_prompt", None)
+        if prompt:
+            pipeline._prompt_embeds = pipeline._target_embeds = encode_prompt(prompt)
+            pipeline._current_prompt = prompt
+        set_strength(_current_strength)
+        _negative_embeds = pipeline._encode_single("")
+        reseed(_seed)
+    return web.json_response({"model": model})
+
+
+async def http_model_cache(request):
+    body = await request.json()
+    path = body.get("path")
+    if not path or not os.path.isdir(path):
+        return bad_request(f"model cache {path!r} is not a directory")
+    os.environ["HF_HOME"] = path
+    return web.json_response({"path": path})
+
+
+def torch_gpu_cache():
+    """The torch GPU backend's (reserved bytes, empty cache) functions, or
+    None if torch isn't installed or has no CUDA or MPS device."""
+    try:
+        import torch
+    except ImportError:
+        return None
+    if torch.cuda.is_available():
+        return torch.cuda.memory_reserved, torch.cuda.empty_cache
+    if torch.backends.mps.is_available():
+        return torch.mps.driver_allocated_memory, torch.mps.empty_cache
+    return None
+
+
+async def http_flush_cache(request):
+    gc.collect()
+    backend = torch_gpu_cache()
+    if backend is None:
+        return web.json_response({"freed_mb": None})
+    reserved, empty_cache = backend
+    before = reserved()
+    empty_cache()
+    freed = max(0, before - reserved()) / (1024 * 1024)
+    return web.json_response({"freed_mb": round(freed, 1)})
+
+
+async def http_params(request):
+    global _seed_locked, _feedback_mode, _feedback_ema
+    body = await request.json()
+    mode = body.get("feedback_mode")
+    if mode is not None and mode not in FEEDBACK_MODES:
+        return bad_request(f"unknown feedback mode {mode!r}")
+    if body.get("prompt"):
+        apply_prompt(body["prompt"])
+    if body.get("feedback") is not None:
+        pipeline.latent_feedback = float(body["feedback"])
+    if body.get("strength") is not None:
+        set_strength(float(body["strength"]))
+    if body.get("seed_locked") is not None:
+        _seed_locked = bool(body["seed_locked"])
+    if mode is not None:
+        _feedback_mode = mode
+        _feedback_ema = None
+    return web.json_response({})
+
+
+async def http_process(request):
+    """Render one frame sent by the app, with optional per-region prompts."""
+    body = await request.json()
+    try:
+        data = base64.b64decode(body["image_base64"])
+        if body.get("compressed"):
+            data = zlib.decompress(data)
+        frame_bgr = decode_image(data, cv2.IMREAD_COLOR)
+        regions = [
+            (decode_image(base64.b64decode(r["mask_base64"]), cv2.IMREAD_GRAYSCALE), r["prompt"])
+            for r in body.get("regions") or []
+        ]
+    except (KeyError, TypeError, ValueError, zlib.error) as e:
+        return bad_request(f"bad frame: {e}")
+    for mask, _ in regions:
+        if mask.shape != frame_bgr.shape[:2]:
+            return bad_request("region masks must be the size of the frame")
+
+    seed = body.get("seed")
+    seed = None if seed is None else int(seed)
+    async with _render_lock:
+        result = await asyncio.get_running_loop().run_in_executor(
+            None, lambda: render_request(frame_bgr, body.get("prompt") or "", seed, regions)
+        )
+
+    _, png = cv2.imencode(".png", result)
+    data = png.tobytes()
+    if body.get("compressed"):
+        data = zlib.compress(data, 1)
+    return web.json_response({"image_base64": base64.b64encode(data).decode("ascii")})
+
+
+def render_request(frame_bgr, prompt, seed, regions):
+    """Render a /process frame with `prompt`, then each region with its own
+    prompt over it. Blocking; called from an executor thread."""
+    apply_prompt(prompt)
+    if seed is not None and seed != _seed:
+        reseed(seed)
+    result = render_at_input_size(frame_bgr, process_frame_advanced)
+    # Later regions are painted over earlier ones, matching the app's
+    # "last region wins" overlap rule.
+    for mask, region_prompt in regions:
+        embeds = encode_prompt(region_prompt)
+        region = render_at_input_size(frame_bgr, lambda f: render_with_embeds(f, embeds))
+        result = np.where(mask[:, :, np.newaxis] >= 128, region, result)
+    return result
+
+
+def build_app():
+    app = web.Application(middlewares=[api_version], client_max_size=64 * 1024 * 1024)
+    app.router.add_get("/", handle_client)
+    app.router.add_get("/health", http_health)
+    app.router.add_get("/models", http_models)
+    app.router.add_post("/model", http_model)
+    app.router.add_post("/model_cache", http_model_cache)
+    app.router.add_post("/flush_cache", http_flush_cache)
+    app.router.add_post("/params", http_params)
+    app.router.add_post("/process", http_process)
+    return app
+
+
+# The pipeline's CoreML models, by attribute.
+COREML_MODELS = ("text_encoder", "unet", "vae_encoder", "vae_decoder")
+
+
+def force_cpu(pipe):
+    """Reload the pipeline's CoreML models to run on the CPU only, for the
+    app's safe mode. Returns False if a model can't be reloaded (e.g. one
+    loaded precompiled, without a package path)."""
+    import coremltools as ct
+
+    moved = False
+    for name in COREML_MODELS:
+        model = getattr(pipe, name, None)
+        if model is None:
+            continue
+        path = getattr(model, "package_path", None)
+        if not path:
+            return False
+        setattr(pipe, name, ct.models.MLModel(path, compute_units=ct.ComputeUnit.CPU_ONLY))
+        moved = True
+    return moved
+
+
+def load_pipeline(args, model):
     coreml_dir = args.coreml_dir or os.path.join(
         os.path.dirname(os.path.abspath(__file__)),
         "streamdiffusion-mac",
         "coreml_models",
     )
-    pipeline = await loop.run_in_executor(
-        None,
-        lambda: Pipeline(
-            model_name=args.model,
-            render_size=args.render_size,
-            output_size=args.render_size,
-            prompt=args.prompt,
-            latent_feedback=args.feedback,
-            coreml_dir=coreml_dir,
-        ),
+    pipe = Pipeline(
+        model_name=model,
+        render_size=args.render_size,
+        output_size=args.render_size,
+        prompt=args.prompt,
+        latent_feedback=args.feedback,
+        coreml_dir=coreml_dir,
     )
+    if args.device == "cpu" and not force_cpu(pipe):
+        raise RuntimeError("this pipeline can't be limited to the CPU, so safe mode is unavailable")
+    return pipe
+
+
+async def main_async(args):
+    global pipeline, _alphas_cumprod, _max_timestep, _negative_embeds, _feedback_mode
+    global _args, _model_name, _seed_locked, _render_lock
+
+    _args = args
+    _render_lock = asyncio.Lock()
+    _feedback_mode = args.feedback_mode
+    _seed_locked = not args.unlock_seed
+    print("LOADING", flush=True)
+
+    # Load pipeline (blocking — runs in executor to not block the event loop)
+    loop = asyncio.get_event_loop()
+    try:
+        pipeline = await loop.run_in_executor(None, lambda: load_pipeline(args, args.model))
+    except RuntimeError as e:
+        # Fail the start rather than run on the GPU while the app reports
+        # safe mode.
+        print(f"ERROR:{e}", flush=True)
+        sys.exit(1)
+    _model_name = args.model
 
     # Compute noise schedule. Use the full 1000-step range (0–999) regardless
     # of what the pipeline's scheduler was initialized with. SDXS uses Euler
@@ -297,7 +712,11 @@ async def main_async(args):
 
     # Encode empty string as default unconditional embedding for CFG
     _negative_embeds = pipeline._encode_single("")
-    # Start WebSocket server
+
+    if args.width and args.height:
+        print(f"PROGRESS:1.0 Warming up at {args.width}x{args.height}", flush=True)
+        await loop.run_in_executor(None, lambda: warm_up(args.width, args.height))
+    # Start the WebSocket and HTTP server
     stop = asyncio.Event()
 
     def signal_handler():
@@ -306,8 +725,12 @@ async def main_async(args):
     loop.add_signal_handler(signal.SIGTERM, signal_handler)
     loop.add_signal_handler(signal.SIGINT, signal_handler)
 
-    async with websockets.serve(handle_client, "127.0.0.1", args.port):
-        print(f"READY:{args.port}", flush=True)
+    runner = web.AppRunner(build_app(), access_log=None)
+    await runner.setup()
+    await web.TCPSite(runner, args.host, args.port).start()
+    try:
+        # The bound port, which differs from args.port when that is 0.
+        print(f"READY:{runner.addresses[0][1]}", flush=True)
 
         # Redirect stdout to stderr — Rust closes the stdout pipe after
         # reading READY, so any future print() to stdout would crash with
@@ -315,10 +738,14 @@ async def main_async(args):
         sys.stdout = sys.stderr
 
         await stop.wait()
+    finally:
+        await runner.cleanup()
 
 
 def main():
     parser = argparse.ArgumentParser(description="Pictaflux Diffusion Sidecar")
+    # Loopback by default; the app passes e.g. 0.0.0.0 for LAN access.
+    parser.add_argument("--host", type=str, default="127.0.0.1")
     parser.add_argument("--port", type=int, default=9824)
     parser.add_argument(
         "--prompt",
@@ -328,9 +755,25 @@ def main():
     parser.add_argument("--model", type=str, default="sdxs")
     parser.add_argument("--render-size", type=int, default=512)
     parser.add_argument("--feedback", type=float, default=0.1)
+    parser.add_argument("--feedback-mode", choices=FEEDBACK_MODES, default="alpha")
     parser.add_argument("--strength", type=float, default=0.5)
     parser.add_argument("--coreml-dir", type=str, default=None)
+    # Working canvas size from the app; one frame is rendered at this size
+    # before READY to warm the pipeline up.
+    parser.add_argument("--width", type=int, default=None)
+    parser.add_argument("--height", type=int, default=None)
+    # Model cache directory; the app also exports it as HF_HOME.
+    parser.add_argument("--model-cache", type=str, default=None)
+    # "cpu" for the app's safe mode, which runs every CoreML model on the CPU;
+    # by default CoreML picks the compute units.
+    parser.add_argument("--device", type=str, default=None)
+    # Advance the live loop's seed every frame instead of holding it.
+    parser.add_argument("--unlock-seed", action="store_true")
+    # Read the prompt from stdin instead, keeping it out of process listings.
+    parser.add_argument("--prompt-stdin", action="store_true")
     args = parser.parse_args()
+    if args.prompt_stdin:
+        args.prompt = sys.stdin.read()
 
     asyncio.run(main_async(args))
 
@@ -0,0 +1,409 @@
+/// Canvas image decoding and preparation for the sidecar.
+///
+/// Diffusion models are picky about input sizes (usually a multiple of 8 or
+/// 64, within a min/max range) and fail with an opaque Python error when the
+/// constraint is violated. Everything here runs before the image leaves Rust
+/// so those failures surface as a clear `SidecarError` instead.
+use crate::error::SidecarError;
+use base64::Engine;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use image::{DynamicImage, GenericImageView, GrayImage, ImageFormat, Rgb, RgbImage};
+use std::fs::File;
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+
+/// What to do when the canvas doesn't meet the model's size constraints.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DimensionFix {
+    /// Return `SidecarError::BadDimensions`.
+    Reject,
+    /// Pad up to the next valid size; the result is cropped back afterwards.
+    Pad,
+    /// Crop down to the previous valid size.
+    Crop,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct DimensionConstraints {
+    pub multiple_of: u32,
+    pub min: u32,
+    pub max: u32,
+    pub fix: DimensionFix,
+}
+
+impl Default for DimensionConstraints {
+    fn default() -> Self {
+        Self {
+            multiple_of: 8,
+            min: 64,
+            max: 2048,
+            fix: DimensionFix::Pad,
+        }
+    }
+}
+
+impl DimensionConstraints {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.multiple_of == 0 {
+            return Err("multiple_of must be at least 1".into());
+        }
+        if self.min > self.max {
+            return Err(format!(
+                "min ({}) must not exceed max ({})",
+                self.min, self.max
+            ));
+        }
+        if self.round_up(self.min) > self.round_down(self.max) {
+            return Err(format!(
+                "No multiple of {} lies between {} and {}",
+                self.multiple_of, self.min, self.max
+            ));
+        }
+        Ok(())
+    }
+
+    fn round_up(&self, v: u32) -> u32 {
+        v.div_ceil(self.multiple_of) * self.multiple_of
+    }
+
+    fn round_down(&self, v: u32) -> u32 {
+        v / self.multiple_of * self.multiple_of
+    }
+
+    fn satisfied_by(&self, v: u32) -> bool {
+        v.is_multiple_of(self.multiple_of) && v >= self.min && v <= self.max
+    }
+
+    fn requirement(&self) -> String {
+        format!(
+            "each side to be a multiple of {} between {} and {}",
+            self.multiple_of, self.min, self.max
+        )
+    }
+
+    /// Check a size against the constraints without fixing it.
+    pub fn check(&self, width: u32, height: u32) -> Result<(), SidecarError> {
+        if self.satisfied_by(width) && self.satisfied_by(height) {
+            Ok(())
+        } else {
+            Err(SidecarError::BadDimensions {
+                width,
+                height,
+                requirement: self.requirement(),
+            })
+        }
+    }
+
+    /// The side length `fix` would produce for `v`, if it can fix it at all.
+    fn target(&self, v: u32) -> Option<u32> {
+        let lo = self.round_up(self.min);
+        let hi = self.round_down(self.max);
+        match self.fix {
+            DimensionFix::Reject => None,
+            DimensionFix::Pad => Some(self.round_up(v).max(lo)).filter(|&t| t <= hi),
+            DimensionFix::Crop => Some(self.round_down(v).min(hi)).filter(|&t| t >= lo),
+        }
+    }
+
+    /// Check `img` against the constraints, padding or cropping it if allowed.
+    pub fn conform(&self, img: DynamicImage) -> Result<PreparedImage, SidecarError> {
+        let (width, height) = img.dimensions();
+        if self.satisfied_by(width) && self.satisfied_by(height) {
+            return Ok(PreparedImage {
+                image: img,
+                original_size: None,
+            });
+        }
+
+        let bad = || SidecarError::BadDimensions {
+            width,
+            height,
+            requirement: self.requirement(),
+        };
+        let target_w = self.target(width).ok_or_else(bad)?;
+        let target_h = self.target(height).ok_or_else(bad)?;
+
+        // Pad with transparent pixels so alpha flattening treats the margin
+        // like any other empty canvas area.
+        let mut fixed = DynamicImage::new_rgba8(target_w, target_h);
+        image::imageops::overlay(&mut fixed, &img.to_rgba8(), 0, 0);
+
+        Ok(PreparedImage {
+            image: fixed,
+            original_size: Some((width, height)),
+        })
+    }
+}
+
+/// A canvas image after dimension fixing.
+pub struct PreparedImage {
+    pub image: DynamicImage,
+    /// Set when the image was padded or cropped; the size to restore.
+    pub original_size: Option<(u32, u32)>,
+}
+
+impl PreparedImage {
+    /// Undo padding on a result image. Cropped images stay cropped since the
+    /// removed pixels were never sent.
+    pub fn restore(&self, result: DynamicImage) -> DynamicImage {
+        match self.original_size {
+            Some((w, h)) if w <= result.width() && h <= result.height() => {
+                result.crop_imm(0, 0, w, h)
+            }
+            _ => result,
+        }
+    }
+}
+
+/// What transparent canvas areas are flattened onto before processing.
+///
+/// Diffusion models only see RGB, so without this transparent pixels reach
+/// the model as whatever garbage color happens to sit under zero alpha.
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Background {
+    #[default]
+    White,
+    Black,
+    Checker,
+    Color([u8; 3]),
+}
+
+/// Side length of one checkerboard square, in pixels.
+const CHECKER_SIZE: u32 = 16;
+
+impl Background {
+    fn pixel_at(&self, x: u32, y: u32) -> [u8; 3] {
+        match self {
+            Background::White => [255, 255, 255],
+            Background::Black => [0, 0, 0],
+            Background::Checker => {
+                if (x / CHECKER_SIZE + y / CHECKER_SIZE).is_multiple_of(2) {
+                    [255, 255, 255]
+                } else {
+                    [204, 204, 204]
+                }
+            }
+            Background::Color(rgb) => *rgb,
+        }
+    }
+
+    /// Composite `img` over this background. Returns the flattened RGB image
+    /// and the original alpha channel, or `None` for the mask if the image
+    /// had no alpha to begin with.
+    pub fn flatten(&self, img: &DynamicImage) -> (DynamicImage, Option<GrayImage>) {
+        if !img.color().has_alpha() {
+            return (img.clone(), None);
+        }
+        let rgba = img.to_rgba8();
+        let (width, height) = rgba.dimensions();
+        let mut mask = GrayImage::new(width, height);
+        let flat = RgbImage::from_fn(width, height, |x, y| {
+            let [r, g, b, a] = rgba.get_pixel(x, y).0;
+            mask.put_pixel(x, y, image::Luma([a]));
+            let bg = self.pixel_at(x, y);
+            let blend =
+                |c: u8, bg: u8| ((c as u16 * a as u16 + bg as u16 * (255 - a as u16)) / 255) as u8;
+            Rgb([blend(r, bg[0]), blend(g, bg[1]), blend(b, bg[2])])
+        });
+        (DynamicImage::ImageRgb8(flat), Some(mask))
+    }
+}
+
+/// Known inputs for checking the processing pipeline without a drawing.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TestPattern {
+    /// Red left to right, green top to bottom.
+    Gradient,
+    Checkerboard,
+    /// Fixed-seed RGB noise, identical on every call.
+    Noise,
+}
+
+impl TestPattern {
+    pub fn generate(self, width: u32, height: u32) -> DynamicImage {
+        let mut state: u32 = 0x9E37_79B9;
+        let img = RgbImage::from_fn(width, height, |x, y| match self {
+            TestPattern::Gradient => Rgb([
+                (x * 255 / width.max(2).saturating_sub(1)).min(255) as u8,
+                (y * 255 / height.max(2).saturating_sub(1)).min(255) as u8,
+                128,
+            ]),
+            TestPattern::Checkerboard => Rgb(Background::Checker.pixel_at(x, y)),
+            TestPattern::Noise => {
+                // xorshift32; pixels are generated in a fixed order.
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                let [r, g, b, _] = state.to_le_bytes();
+                Rgb([r, g, b])
+            }
+        });
+        DynamicImage::ImageRgb8(img)
+    }
+}
+
+/// Reapply an alpha mask captured by `Background::flatten` to a result.
+/// A result whose size doesn't match the mask is returned untouched.
+pub fn apply_alpha(result: DynamicImage, mask: &GrayImage) -> DynamicImage {
+    if result.dimensions() != mask.dimensions() {
+        return result;
+    }
+    let mut rgba = result.to_rgba8();
+    for (pixel, alpha) in rgba.pixels_mut().zip(mask.pixels()) {
+        pixel.0[3] = alpha.0[0];
+    }
+    DynamicImage::ImageRgba8(rgba)
+}
+
+/// A prompt for part of the canvas, as sent by the frontend. The mask is an
+/// image the size of the canvas; pixels at least half-bright are in the
+/// region.
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct PromptRegion {
+    pub mask_base64: String,
+    pub prompt: String,
+}
+
+/// A decoded `PromptRegion` whose mask is 255 inside the region, 0 outside.
+pub struct RegionMask {
+    pub mask: GrayImage,
+    pub prompt: String,
+}
+
+/// Decode region masks for a `width`x`height` canvas. Where regions
+/// overlap the later one wins, so the masks returned never share a pixel.
+pub fn decode_regions(
+    regions: Vec<PromptRegion>,
+    width: u32,
+    height: u32,
+) -> Result<Vec<RegionMask>, SidecarError> {
+    let mut decoded: Vec<RegionMask> = Vec::with_capacity(regions.len());
+    for (i, region) in regions.into_iter().enumerate() {
+        let (img, _) = decode_base64(&region.mask_base64)
+            .map_err(|e| SidecarError::InvalidImage(format!("region {} mask: {}", i, e)))?;
+        if img.dimensions() != (width, height) {
+            return Err(SidecarError::InvalidImage(format!(
+                "region {} mask is {}x{}, but the canvas is {}x{}",
+                i,
+                img.width(),
+                img.height(),
+                width,
+                height
+            )));
+        }
+        let mut mask = img.to_luma8();
+        threshold_mask(&mut mask);
+        for earlier in &mut decoded {
+            for (theirs, ours) in earlier.mask.pixels_mut().zip(mask.pixels()) {
+                if ours.0[0] != 0 {
+                    theirs.0[0] = 0;
+                }
+            }
+        }
+        decoded.push(RegionMask {
+            mask,
+            prompt: region.prompt,
+        });
+    }
+    Ok(decoded)
+}
+
+/// Snap a mask to 255 where it is at least half-bright and 0 elsewhere.
+/// Also applied after padding or cropping, so a resized mask stays binary.
+pub fn threshold_mask(mask: &mut GrayImage) {
+    for pixel in mask.pixels_mut() {
+        pixel.0[0] = if pixel.0[0] >= 128 { 255 } else { 0 };
+    }
+}
+
+/// Formats accepted from the canvas and from the sidecar.
+const SUPPORTED_FORMATS: [ImageFormat; 4] = [
+    ImageFormat::Png,
+    ImageFormat::Jpeg,
+    ImageFormat::WebP,
+    ImageFormat::Bmp,
+];
+
+/// Short lowercase name for messages and payloads, e.g. "png".
+pub fn format_name(format: ImageFormat) -> &'static str {
+    format
+        .extensions_str()
+        .first()
+        .copied()
+        .unwrap_or("unknown")
+}
+
+/// Whether `path` is a file in one of the supported formats, judged by its
+/// magic bytes rather than its extension.
+pub fn is_supported_image(path: &Path) -> bool {
+    let mut header = [0u8; 32];
+    let Ok(read) = File::open(path).and_then(|mut file| file.read(&mut header)) else {
+        return false;
+    };
+    image::guess_format(&header[..read]).is_ok_and(|format| SUPPORTED_FORMATS.contains(&format))
+}
+
+/// Decode a base64 image, accepting an optional `data:` URL prefix.
+/// Returns the image along with the format it was encoded in.
+pub fn decode_base64(data: &str) -> Result<(DynamicImage, ImageFormat), SidecarError> {
+    let payload = match data.split_once(";base64,") {
+        Some((prefix, rest)) if prefix.starts_with("data:") => rest,
+        _ => data,
+    };
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(payload.trim())
+        .map_err(|e| SidecarError::InvalidImage(format!("bad base64: {}", e)))?;
+    decode_bytes(&bytes)
+}
+
+/// Decode PNG, JPEG, WebP, or BMP data, detecting the format from its
+/// magic bytes.
+pub fn decode_bytes(bytes: &[u8]) -> Result<(DynamicImage, ImageFormat), SidecarError> {
+    let format = image::guess_format(bytes)
+        .map_err(|_| SidecarError::InvalidImage("unrecognized image data".into()))?;
+    if !SUPPORTED_FORMATS.contains(&format) {
+        return Err(SidecarError::InvalidImage(format!(
+            "unsupported format {}; expected PNG, JPEG, WebP, or BMP",
+            format_name(format)
+        )));
+    }
+    let img = image::load_from_memory_with_format(bytes, format)
+        .map_err(|e| SidecarError::InvalidImage(e.to_string()))?;
+    Ok((img, format))
+}
+
+pub fn encode_png(img: &DynamicImage) -> Result<Vec<u8>, SidecarError> {
+    let mut bytes = Vec::new();
+    img.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+        .map_err(|e| SidecarError::InvalidImage(e.to_string()))?;
+    Ok(bytes)
+}
+
+/// Encode an image as base64 PNG.
+pub fn encode_base64_png(img: &DynamicImage) -> Result<String, SidecarError> {
+    Ok(base64::engine::general_purpose::STANDARD.encode(encode_png(img)?))
+}
+
+/// Zlib-compress a frame for the sidecar. PNG data is already deflated, so
+/// the gain is usually small; the "process-perf" event reports whether it
+/// is worth it for a given canvas.
+pub fn compress_frame(bytes: &[u8]) -> Result<Vec<u8>, SidecarError> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::fast());
+    encoder
+        .write_all(bytes)
+        .and_then(|_| encoder.finish())
+        .map_err(|e| SidecarError::Other(format!("Frame compression failed: {}", e)))
+}
+
+pub fn decompress_frame(bytes: &[u8]) -> Result<Vec<u8>, SidecarError> {
+    let mut out = Vec::new();
+    ZlibDecoder::new(bytes)
+        .read_to_end(&mut out)
+        .map_err(|e| SidecarError::InvalidImage(format!("bad compressed frame: {}", e)))?;
+    Ok(out)
+}
@@ -0,0 +1,64 @@
+/// Human-readable rendering of the sidecar spawn command, for diagnostics.
+///
+/// Most "it won't start" reports come down to which interpreter, script,
+/// arguments, or Python/ML environment variables were in effect, so the
+/// rendered line includes all of them, with secrets redacted.
+use std::path::Path;
+
+/// Inherited environment variables that influence the sidecar.
+const ENV_PREFIXES: [&str; 7] = [
+    "PYTHON",
+    "VIRTUAL_ENV",
+    "HF_",
+    "CUDA_",
+    "PYTORCH_",
+    "TORCH_",
+    "PATH",
+];
+
+/// Name fragments marking a variable's value as a secret.
+const SECRET_MARKERS: [&str; 5] = ["TOKEN", "KEY", "SECRET", "PASSWORD", "AUTH"];
+
+/// Render `NAME=value ... program arg ...` with shell-style quoting.
+pub fn describe(program: &Path, args: &[String]) -> String {
+    let mut parts: Vec<String> = relevant_env()
+        .into_iter()
+        .map(|(name, value)| format!("{}={}", name, quote(&value)))
+        .collect();
+    parts.push(quote(&program.display().to_string()));
+    parts.extend(args.iter().map(|arg| quote(arg)));
+    parts.join(" ")
+}
+
+fn relevant_env() -> Vec<(String, String)> {
+    let mut vars: Vec<(String, String)> = std::env::vars()
+        .filter(|(name, _)| ENV_PREFIXES.iter().any(|p| name.starts_with(p)))
+        .map(|(name, value)| {
+            let value = if is_secret(&name) {
+                "<redacted>".to_string()
+            } else {
+                value
+            };
+            (name, value)
+        })
+        .collect();
+    vars.sort();
+    vars
+}
+
+fn is_secret(name: &str) -> bool {
+    let upper = name.to_ascii_uppercase();
+    SECRET_MARKERS.iter().any(|m| upper.contains(m))
+}
+
+/// Quote `s` for a POSIX shell, leaving plain words alone.
+pub fn quote(s: &str) -> String {
+    let plain = !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:=+,@%".contains(c));
+    if plain {
+        s.to_string()
+    } else {
+        format!("'{}'", s.replace('\'', r"'\''"))
+    }
+}
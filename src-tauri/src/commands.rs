@@ -1,24 +1,696 @@
-use crate::state::{AppState, SidecarStatus};
+use crate::canvas::{
+    self, Background, DimensionConstraints, PromptRegion, RegionMask, TestPattern,
+};
+use crate::cmdline;
+use crate::config::{self, PressureCurveFile, PressureProfile, Settings};
+use crate::error::SidecarError;
+use crate::error_log::ErrorRecord;
+use crate::events;
+use crate::gpu;
+use crate::log_buffer::{LogBuffer, LogLine};
+use crate::loop_capture::{self, LoopCapture};
+use crate::low_power::{self, LowPowerConfig};
+use crate::models::{self, ModelCache, ModelInfo};
+use crate::permissions::{self, InputMonitoringPermission};
+use crate::persist;
+use crate::power;
+use crate::progress::ProgressReporter;
+use crate::prompt::{self, ValidatedPrompt};
+use crate::recording::{self, RecordedEvent, Recorder, ReplayCursor};
+use crate::screenshot;
+use crate::sidecar_client::{
+    self, ClientError, FlushCacheResponse, ParamsRequest, ProcessRegion, ProcessRequest,
+    SidecarClient,
+};
+use crate::sidecar_protocol::{self, LineParser, Metric, SidecarLine};
+use crate::stability::{RunEnd, StabilityReport};
+use crate::state::{
+    AppState, CancelToken, FeedbackMode, LaunchArgs, LockExt, ProcessingMode, ReadyInfo,
+    SidecarState, SidecarStatus, TabletDevice, TabletProbe, DEFAULT_BIND_HOST, DEFAULT_PORT,
+    MAX_METRICS,
+};
+use base64::Engine;
+use image::{DynamicImage, ImageFormat};
+use std::collections::{BTreeMap, VecDeque};
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
-use tauri::State;
-use tokio::io::AsyncBufReadExt;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tauri::{AppHandle, Manager, State};
+use tauri_plugin_shell::ShellExt;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+use tokio::sync::Notify;
 
+#[derive(Clone, serde::Serialize)]
+pub struct ProcessResult {
+    pub image_base64: String,
+    /// Seed the frame was generated with; pass it back to reproduce it.
+    pub seed: u64,
+    /// Time spent in the sidecar round-trip.
+    pub inference_ms: u64,
+    pub model: String,
+    /// Dispatch order of the `process_canvas` call, compared against
+    /// `get_frame_generation` to spot stragglers; 0 for test patterns.
+    pub frame_id: u64,
+}
+
+/// The seed for a frame: `requested` if given, else the stored seed while
+/// the seed is locked, else a fresh one. Remembered for the next frame.
+fn next_seed(state: &AppState, requested: Option<u64>) -> u64 {
+    let seed = match requested {
+        Some(seed) => seed,
+        None if state.seed_locked.load(Ordering::Relaxed) => state.seed.load(Ordering::Relaxed),
+        None => random_seed(),
+    };
+    state.seed.store(seed, Ordering::Relaxed);
+    seed
+}
+
+/// A fresh seed for frames that don't pin one. Kept to 53 bits so it
+/// survives the round-trip through a JavaScript number.
+fn random_seed() -> u64 {
+    use std::hash::{BuildHasher, Hasher};
+    std::collections::hash_map::RandomState::new()
+        .build_hasher()
+        .finish()
+        >> 11
+}
+
+/// `prompt_override` renders just this frame with a different prompt,
+/// leaving the live session's prompt as it was. `regions` apply their own
+/// prompts to masked parts of the canvas; later regions take precedence
+/// where masks overlap. Fails with `Superseded` when a newer call's frame
+/// was delivered before this one finished, so the newest frame always wins.
 #[tauri::command]
-pub fn process_canvas(
+#[allow(clippy::too_many_arguments)]
+pub async fn process_canvas(
     image_base64: String,
     prompt: String,
+    prompt_override: Option<String>,
+    regions: Option<Vec<PromptRegion>>,
+    background: Option<Background>,
+    seed: Option<u64>,
+    app: AppHandle,
     state: State<'_, AppState>,
-) -> Result<String, String> {
-    *state.last_prompt.lock().map_err(|e| e.to_string())? = prompt;
-    Ok(image_base64)
+) -> Result<ProcessResult, SidecarError> {
+    recorded_async(&state, "process_canvas", async {
+        let cancel = state.canvas_cancel.lock_or_recover().clone();
+        let (decoded, source_format) = canvas::decode_base64(&image_base64)?;
+        let regions = canvas::decode_regions(
+            regions.unwrap_or_default(),
+            decoded.width(),
+            decoded.height(),
+        )?;
+        // Only frames that made it this far count as dispatched; one rejected
+        // up front never reaches the sidecar.
+        let render = RenderGuard::acquire(&state)?;
+        let frame_id = state.frame_generation.fetch_add(1, Ordering::SeqCst) + 1;
+        let rendered = render_frame(
+            &app,
+            &state,
+            render,
+            decoded,
+            source_format,
+            prompt,
+            prompt_override,
+            regions,
+            background,
+            Some(next_seed(&state, seed)),
+            Some(&cancel),
+        )
+        .await?;
+        // A clear that lands after the round trip still wins over this frame.
+        if cancel.is_cancelled() {
+            return Err(SidecarError::Cancelled);
+        }
+        let image_base64 = canvas::encode_base64_png(&rendered.image)?;
+        // Older frames still in flight are worth showing until a newer one
+        // arrives; only a newer frame already delivered makes this one stale.
+        let latest = state.frame_delivered.fetch_max(frame_id, Ordering::SeqCst);
+        if latest > frame_id {
+            return Err(SidecarError::Superseded { frame_id, latest });
+        }
+        Ok(ProcessResult {
+            image_base64,
+            seed: rendered.seed,
+            inference_ms: rendered.inference_ms,
+            model: rendered.model,
+            frame_id,
+        })
+    })
+    .await
+}
+
+/// An error a command can return.
+trait CommandError: std::fmt::Display {
+    /// Whether it belongs in the error history.
+    fn is_failure(&self) -> bool {
+        true
+    }
+}
+
+impl CommandError for String {}
+
+impl CommandError for SidecarError {
+    /// Cancellations are the user's doing, and superseded frames routine
+    /// during live drawing, not failures.
+    fn is_failure(&self) -> bool {
+        !matches!(
+            self,
+            SidecarError::Cancelled | SidecarError::Superseded { .. }
+        )
+    }
+}
+
+fn record_command_error(state: &AppState, command: &str, error: &impl CommandError) {
+    if error.is_failure() {
+        state.record_error(command, error);
+    }
+}
+
+/// Run a command's body, adding any error it returns to the history
+/// `get_recent_errors` reads. Every fallible command goes through this or
+/// `recorded_async`, so none has to remember to record its own errors.
+fn recorded<T, E: CommandError>(
+    state: &AppState,
+    command: &str,
+    body: impl FnOnce() -> Result<T, E>,
+) -> Result<T, E> {
+    body().inspect_err(|e| record_command_error(state, command, e))
+}
+
+/// `recorded` for async commands.
+async fn recorded_async<T, E: CommandError>(
+    state: &AppState,
+    command: &str,
+    body: impl Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+    body.await
+        .inspect_err(|e| record_command_error(state, command, e))
+}
+
+/// Counts a render as in flight for as long as it is alive, so the status
+/// can report the sidecar as busy.
+struct RenderGuard<'a> {
+    state: &'a AppState,
+}
+
+impl<'a> RenderGuard<'a> {
+    /// Fails with `NotReady` unless the sidecar is `Ready`.
+    fn acquire(state: &'a AppState) -> Result<Self, SidecarError> {
+        let mut sidecar = state.sidecar.lock_or_recover();
+        if !matches!(sidecar.status, SidecarStatus::Ready) {
+            return Err(SidecarError::NotReady {
+                status: sidecar.status.clone(),
+            });
+        }
+        sidecar.renders_in_flight += 1;
+        Ok(Self { state })
+    }
+
+    /// Like `acquire`, but first waits for renders already in flight, so a
+    /// batch takes turns with the live loop instead of piling onto it.
+    async fn acquire_idle(state: &'a AppState, cancel: &CancelToken) -> Result<Self, SidecarError> {
+        loop {
+            {
+                let mut sidecar = state.sidecar.lock_or_recover();
+                if !matches!(sidecar.status, SidecarStatus::Ready) {
+                    return Err(SidecarError::NotReady {
+                        status: sidecar.status.clone(),
+                    });
+                }
+                if sidecar.renders_in_flight == 0 {
+                    sidecar.renders_in_flight = 1;
+                    return Ok(Self { state });
+                }
+            }
+            if cancel.is_cancelled() {
+                return Err(SidecarError::Cancelled);
+            }
+            tokio::time::sleep(RENDER_IDLE_POLL).await;
+        }
+    }
+}
+
+/// How often `RenderGuard::acquire_idle` checks for the sidecar to be free.
+const RENDER_IDLE_POLL: std::time::Duration = std::time::Duration::from_millis(25);
+
+impl Drop for RenderGuard<'_> {
+    fn drop(&mut self) {
+        let mut sidecar = self.state.sidecar.lock_or_recover();
+        sidecar.renders_in_flight = sidecar.renders_in_flight.saturating_sub(1);
+    }
+}
+
+/// Default side length of a generated test pattern.
+const TEST_PATTERN_SIZE: u32 = 512;
+
+#[derive(Clone, serde::Serialize)]
+pub struct TestPatternResult {
+    pub result: ProcessResult,
+    /// Wall time for the whole round trip, including encode and decode.
+    pub total_ms: u64,
+}
+
+/// Run a generated image through the same path as `process_canvas`, for
+/// smoke tests and bug reports that need a deterministic input. The seed is
+/// pinned so repeated runs are comparable.
+#[tauri::command]
+pub async fn process_test_pattern(
+    kind: TestPattern,
+    width: Option<u32>,
+    height: Option<u32>,
+    prompt: String,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<TestPatternResult, SidecarError> {
+    recorded_async(&state, "process_test_pattern", async {
+        let started = Instant::now();
+        let image = kind.generate(
+            width.unwrap_or(TEST_PATTERN_SIZE).max(1),
+            height.unwrap_or(TEST_PATTERN_SIZE).max(1),
+        );
+        // Round-trip through base64 PNG so the decode half is exercised too.
+        let (decoded, source_format) = canvas::decode_base64(&canvas::encode_base64_png(&image)?)?;
+        let rendered = render_frame(
+            &app,
+            &state,
+            RenderGuard::acquire(&state)?,
+            decoded,
+            source_format,
+            prompt,
+            None,
+            Vec::new(),
+            None,
+            Some(0),
+            None,
+        )
+        .await?;
+        let result = ProcessResult {
+            image_base64: canvas::encode_base64_png(&rendered.image)?,
+            seed: rendered.seed,
+            inference_ms: rendered.inference_ms,
+            model: rendered.model,
+            frame_id: 0,
+        };
+        Ok(TestPatternResult {
+            result,
+            total_ms: started.elapsed().as_millis() as u64,
+        })
+    })
+    .await
+}
+
+/// Id of the newest `process_canvas` call dispatched to the sidecar. A
+/// result whose `frame_id` is lower than one already shown is a straggler
+/// the UI can ignore.
+#[tauri::command]
+pub fn get_frame_generation(state: State<'_, AppState>) -> u64 {
+    state.frame_generation.load(Ordering::SeqCst)
+}
+
+/// Drop every in-flight `process_canvas` call, so a result computed from
+/// the old drawing can't repaint the canvas after it was cleared. The
+/// dropped calls fail with `Cancelled`; later calls are unaffected.
+#[tauri::command]
+pub fn clear_canvas(app: AppHandle, state: State<'_, AppState>) -> Result<(), String> {
+    let previous = std::mem::replace(
+        &mut *state.canvas_cancel.lock_or_recover(),
+        Arc::new(CancelToken::new()),
+    );
+    previous.cancel();
+    events::emit_sidecar(&app, "canvas-cleared", ());
+    Ok(())
+}
+
+/// A processed frame, before encoding for the caller.
+struct RenderedFrame {
+    image: DynamicImage,
+    seed: u64,
+    inference_ms: u64,
+    model: String,
+}
+
+/// The processing pipeline shared by `process_canvas` and batch runs:
+/// conform, flatten, round-trip through the sidecar, and undo the
+/// preparation on the result. The caller's `render` guard has already
+/// checked the sidecar is ready, so this never reaches for a port that is
+/// mid-startup. Notifying `cancel` abandons the
+/// round-trip. `prompt` becomes the session's prompt unless
+/// `prompt_override` replaces it for this frame only; `regions` go through
+/// the same padding or cropping as the frame.
+#[allow(clippy::too_many_arguments)]
+async fn render_frame(
+    app: &AppHandle,
+    state: &AppState,
+    _render: RenderGuard<'_>,
+    decoded: DynamicImage,
+    source_format: ImageFormat,
+    prompt: String,
+    prompt_override: Option<String>,
+    regions: Vec<RegionMask>,
+    background: Option<Background>,
+    seed: Option<u64>,
+    cancel: Option<&CancelToken>,
+) -> Result<RenderedFrame, SidecarError> {
+    let started = Instant::now();
+    state.touch_activity();
+    let prompt = match prompt_override {
+        Some(prompt) => checked_prompt(state, &prompt)?,
+        None => {
+            let prompt = checked_prompt(state, &prompt)?;
+            *state.last_prompt.lock_or_recover() = prompt.clone();
+            remember_session(app, state, Some(&prompt), None, None, None);
+            prompt
+        }
+    };
+    let seed = seed.unwrap_or_else(random_seed);
+    let (addr, model) = {
+        let sidecar = state.sidecar.lock_or_recover();
+        let model = sidecar
+            .ready
+            .as_ref()
+            .map(|ready| ready.model.clone())
+            .unwrap_or_default();
+        (
+            sidecar_client::connect_addr(sidecar.host, sidecar.port),
+            model,
+        )
+    };
+    let constraints = state.dimensions.lock_or_recover().clone();
+    let compress = state.compress_frames.load(Ordering::Relaxed);
+    let mode = *state.mode.lock_or_recover();
+
+    events::record(
+        app,
+        "process-canvas",
+        &serde_json::json!({
+            "width": decoded.width(),
+            "height": decoded.height(),
+            "background": background,
+            "mode": mode,
+            "prompt": prompt,
+            "region_prompts": regions.iter().map(|r| &r.prompt).collect::<Vec<_>>(),
+        }),
+    );
+    let prepared = constraints.conform(decoded)?;
+    let regions = regions
+        .into_iter()
+        .map(|region| {
+            let mut mask = constraints
+                .conform(DynamicImage::ImageLuma8(region.mask))?
+                .image
+                .to_luma8();
+            canvas::threshold_mask(&mut mask);
+            Ok(RegionMask {
+                mask,
+                prompt: region.prompt,
+            })
+        })
+        .collect::<Result<Vec<_>, SidecarError>>()?;
+    let (flat, alpha) = match mode {
+        ProcessingMode::Img2Img => background.unwrap_or_default().flatten(&prepared.image),
+        // Only the size reaches the model, and the result is opaque.
+        ProcessingMode::Txt2Img => (
+            DynamicImage::new_rgb8(prepared.image.width(), prepared.image.height()),
+            None,
+        ),
+    };
+
+    let frame = canvas::encode_png(&flat)?;
+    let outgoing = if compress {
+        canvas::compress_frame(&frame)?
+    } else {
+        frame.clone()
+    };
+    let encoded_ms = started.elapsed().as_millis() as u64;
+
+    let masks = regions
+        .iter()
+        .map(|region| {
+            Ok(ProcessRegion {
+                mask_base64: canvas::encode_base64_png(&DynamicImage::ImageLuma8(
+                    region.mask.clone(),
+                ))?,
+                prompt: &region.prompt,
+            })
+        })
+        .collect::<Result<Vec<_>, SidecarError>>()?;
+    let request = ProcessRequest {
+        image_base64: base64::engine::general_purpose::STANDARD.encode(&outgoing),
+        compressed: compress,
+        prompt: &prompt,
+        seed,
+        regions: masks,
+    };
+
+    // The timeout keeps a hung sidecar from holding the command (and the
+    // frontend awaiting it) forever.
+    let (process_timeout, client) = {
+        let settings = state.settings.lock_or_recover();
+        (
+            settings.process_timeout_secs,
+            SidecarClient::new(addr, settings.client.clone()),
+        )
+    };
+    let inference_started = Instant::now();
+    let round_trip = client.process(&request, std::time::Duration::from_secs(process_timeout));
+    let response = tokio::select! {
+        response = round_trip => response.map_err(|e| match e {
+            ClientError::Unreachable(e) if e.is_timeout() => SidecarError::ProcessTimeout {
+                secs: process_timeout,
+            },
+            e => SidecarError::Client(e),
+        })?,
+        _ = async {
+            match cancel {
+                Some(cancel) => cancel.cancelled().await,
+                None => std::future::pending().await,
+            }
+        } => return Err(SidecarError::Cancelled),
+    };
+    let inference_ms = inference_started.elapsed().as_millis() as u64;
+    let response = base64::engine::general_purpose::STANDARD
+        .decode(response.trim())
+        .map_err(|e| SidecarError::InvalidImage(format!("bad base64 from sidecar: {}", e)))?;
+    let response = if compress {
+        canvas::decompress_frame(&response)?
+    } else {
+        response
+    };
+
+    let (mut result, _) = canvas::decode_bytes(&response)?;
+    if let Some(mask) = &alpha {
+        result = canvas::apply_alpha(result, mask);
+    }
+    let result = prepared.restore(result);
+
+    events::emit_sidecar(
+        app,
+        "process-perf",
+        ProcessPerfPayload {
+            source_format: canvas::format_name(source_format),
+            raw_bytes: frame.len(),
+            sent_bytes: outgoing.len(),
+            compressed: compress,
+            encode_ms: encoded_ms,
+            total_ms: started.elapsed().as_millis() as u64,
+        },
+    );
+    Ok(RenderedFrame {
+        image: result,
+        seed,
+        inference_ms,
+        model,
+    })
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize)]
+pub struct BatchSummary {
+    pub completed: usize,
+    pub remaining: usize,
+    pub failed: usize,
+}
+
+/// Process image files one after another, writing each result as
+/// `<stem>.png` in `output_dir`. Reports through "progress"; stops between
+/// files (or mid-file) on `cancel_batch`. Each file waits for any live
+/// render in flight to finish first. If the sidecar stops being ready, the
+/// batch stops rather than failing every remaining file.
+#[tauri::command]
+pub async fn process_batch(
+    paths: Vec<String>,
+    output_dir: String,
+    prompt: String,
+    background: Option<Background>,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<BatchSummary, String> {
+    recorded_async(&state, "process_batch", async {
+        let cancel = Arc::new(CancelToken::new());
+        {
+            let mut batch = state.batch_cancel.lock_or_recover();
+            if batch.is_some() {
+                return Err("A batch is already running".into());
+            }
+            *batch = Some(cancel.clone());
+        }
+        let output_dir = PathBuf::from(output_dir);
+        if let Err(e) = tokio::fs::create_dir_all(&output_dir).await {
+            *state.batch_cancel.lock_or_recover() = None;
+            return Err(format!("Failed to create {}: {}", output_dir.display(), e));
+        }
+        power::hold_for_run(&state, power::BATCH);
+
+        let progress = ProgressReporter::new(&app, "batch");
+        let total = paths.len();
+        let mut summary = BatchSummary {
+            remaining: total,
+            ..Default::default()
+        };
+        let mut stopped = None;
+        for (i, path) in paths.iter().enumerate() {
+            if cancel.is_cancelled() {
+                break;
+            }
+            let path = Path::new(path);
+            progress.report(
+                "processing",
+                i as f32 / total as f32,
+                format!("Processing {}", path.display()),
+            );
+            let outcome = async {
+                let bytes = tokio::fs::read(path)
+                    .await
+                    .map_err(|e| SidecarError::Other(e.to_string()))?;
+                let (decoded, format) = canvas::decode_bytes(&bytes)?;
+                let rendered = render_frame(
+                    &app,
+                    &state,
+                    RenderGuard::acquire_idle(&state, &cancel).await?,
+                    decoded,
+                    format,
+                    prompt.clone(),
+                    None,
+                    Vec::new(),
+                    background,
+                    Some(next_seed(&state, None)),
+                    Some(&cancel),
+                )
+                .await?;
+                let stem = path.file_stem().unwrap_or(path.as_os_str());
+                let out = output_dir.join(format!("{}.png", stem.to_string_lossy()));
+                tokio::fs::write(&out, canvas::encode_png(&rendered.image)?)
+                    .await
+                    .map_err(|e| SidecarError::Other(format!("{}: {}", out.display(), e)))
+            }
+            .await;
+            match outcome {
+                Ok(()) => summary.completed += 1,
+                Err(SidecarError::Cancelled) => break,
+                Err(e @ SidecarError::NotReady { .. }) => {
+                    stopped = Some(e);
+                    break;
+                }
+                Err(e) => {
+                    eprintln!("[batch] {}: {}", path.display(), e);
+                    state.record_error("process_batch", format!("{}: {}", path.display(), e));
+                    summary.failed += 1;
+                }
+            }
+            summary.remaining -= 1;
+        }
+
+        *state.batch_cancel.lock_or_recover() = None;
+        power::release_for_run(&state, power::BATCH);
+        if let Some(e) = stopped {
+            let message = format!(
+                "Batch stopped with {} of {} files left: {}",
+                summary.remaining, total, e
+            );
+            progress.report("failed", 1.0, message.clone());
+            return Err(message);
+        }
+        if cancel.is_cancelled() {
+            progress.report("cancelled", 1.0, "Batch cancelled");
+            events::emit_sidecar(&app, "batch-cancelled", summary.clone());
+        } else {
+            progress.report("done", 1.0, format!("Processed {} files", total));
+        }
+        Ok(summary)
+    })
+    .await
+}
+
+/// Stop the running batch after (or during) its current file. The batch
+/// command itself resolves with the partial summary.
+#[tauri::command]
+pub fn cancel_batch(state: State<'_, AppState>) -> Result<bool, String> {
+    let batch = state.batch_cancel.lock_or_recover();
+    match batch.as_ref() {
+        Some(cancel) => {
+            cancel.cancel();
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Serialized payload for the "process-perf" event.
+#[derive(Clone, serde::Serialize)]
+struct ProcessPerfPayload {
+    /// Format the canvas arrived in; frames are always sent as PNG.
+    source_format: &'static str,
+    /// PNG frame size before compression.
+    raw_bytes: usize,
+    /// Bytes actually sent to the sidecar.
+    sent_bytes: usize,
+    compressed: bool,
+    /// Time spent decoding, fixing, and encoding the outgoing frame.
+    encode_ms: u64,
+    total_ms: u64,
+}
+
+/// Toggle zlib compression of frames sent to the sidecar.
+#[tauri::command]
+pub fn set_frame_compression(enabled: bool, state: State<'_, AppState>) {
+    state.compress_frames.store(enabled, Ordering::Relaxed);
+}
+
+#[tauri::command]
+pub fn set_dimension_constraints(
+    constraints: DimensionConstraints,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    recorded(&state, "set_dimension_constraints", || {
+        constraints.validate()?;
+        *state.dimensions.lock_or_recover() = constraints;
+        Ok(())
+    })
 }
 
 #[derive(serde::Serialize)]
 pub struct SidecarStartResult {
     pub port: u16,
+    /// Set by `validate_only` when `port` is held by a process a real start
+    /// would kill first, such as an orphaned sidecar; names the holder.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port_cleanup: Option<String>,
 }
 
+/// Start the sidecar. With `validate_only`, run the same pre-flight checks a
+/// real start does and return without spawning anything; a port held by a
+/// process the start would kill is reported in `port_cleanup` rather than
+/// failing. `bind_host`
+/// defaults to loopback; any other address makes the server reachable from
+/// the network, and "sidecar-exposed-warning" is emitted once it is up.
+/// `python_path` overrides `Settings::python_path` for this start and its
+/// relaunches.
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn start_sidecar(
     port: u16,
     prompt: String,
@@ -26,152 +698,2738 @@ pub async fn start_sidecar(
     strength: f32,
     model: String,
     render_size: u16,
+    validate_only: Option<bool>,
+    feedback_mode: Option<FeedbackMode>,
+    width: Option<u32>,
+    height: Option<u32>,
+    script: Option<String>,
+    python_path: Option<String>,
+    safe_mode: Option<bool>,
+    bind_host: Option<String>,
+    app: AppHandle,
     state: State<'_, AppState>,
 ) -> Result<SidecarStartResult, String> {
-    // Check if already running
-    {
-        let sidecar = state.sidecar.lock().map_err(|e| e.to_string())?;
-        if sidecar.child.is_some() {
-            return Err("Sidecar is already running".into());
-        }
-    }
-
-    // Safety net: kill any orphaned process on the target port (e.g. from
-    // a previous crash or unclean shutdown).
-    let _ = tokio::process::Command::new("lsof")
-        .args(["-ti", &format!(":{}", port)])
-        .output()
-        .await
-        .map(|output| {
-            if let Ok(pids) = String::from_utf8(output.stdout) {
-                for pid in pids.lines() {
-                    if let Ok(pid) = pid.trim().parse::<u32>() {
-                        let _ = std::process::Command::new("kill")
-                            .args(["-9", &pid.to_string()])
-                            .output();
-                    }
-                }
+    recorded_async(&state, "start_sidecar", async {
+        let bind_host = parse_bind_host(bind_host.as_deref())?;
+        let prompt = checked_prompt(&state, &prompt)?;
+        let canvas_size = match (width, height) {
+            (Some(w), Some(h)) => {
+                let constraints = state.dimensions.lock_or_recover().clone();
+                constraints.check(w, h).map_err(|e| e.to_string())?;
+                Some((w, h))
             }
-        });
-    // Brief pause so the OS can release the socket
-    tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+            (None, None) => None,
+            _ => return Err("width and height must be given together".into()),
+        };
+        if validate_only.unwrap_or(false) {
+            let checked = preflight(
+                &app,
+                bind_host,
+                port,
+                script.as_deref(),
+                python_path.as_deref().map(Path::new),
+            )
+            .await;
+            // A held port only fails validation if a start wouldn't free it.
+            let port_cleanup = match checked {
+                Ok(_) => None,
+                Err(SidecarError::PortInUse { holder, .. }) if start_frees_port(port) => {
+                    Some(holder.unwrap_or_else(|| "an unidentified process".into()))
+                }
+                Err(e) => return Err(e.to_string()),
+            };
+            return Ok(SidecarStartResult { port, port_cleanup });
+        }
 
-    // Determine paths
-    let sidecar_dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
-        .parent()
-        .unwrap()
-        .join("sidecar");
-    let python_path = sidecar_dir.join(".venv/bin/python3");
-    let script_path = sidecar_dir.join("diffusion_server.py");
+        let feedback_mode = {
+            let mut current = state.feedback_mode.lock_or_recover();
+            *current = feedback_mode.unwrap_or(*current);
+            *current
+        };
+        let args = LaunchArgs {
+            port,
+            bind_host,
+            prompt: prompt.clone(),
+            feedback,
+            feedback_mode,
+            strength,
+            model,
+            render_size,
+            canvas_size,
+            script,
+            python_path: python_path.map(PathBuf::from),
+            safe_mode: safe_mode.unwrap_or(false),
+        };
+        let started = start_with_args(&app, &state, args).await?;
+        // Only a start that worked is worth repeating on the next launch.
+        remember_session(
+            &app,
+            &state,
+            Some(&prompt),
+            Some(feedback),
+            Some(strength),
+            Some(port),
+        );
+        Ok(started)
+    })
+    .await
+}
 
-    if !python_path.exists() {
-        return Err(format!(
-            "Python venv not found at {}. Run the sidecar setup first.",
-            python_path.display()
-        ));
-    }
-    if !script_path.exists() {
+/// The address to bind, from `start_sidecar`'s `bind_host`.
+fn parse_bind_host(host: Option<&str>) -> Result<IpAddr, String> {
+    let Some(host) = host else {
+        return Ok(DEFAULT_BIND_HOST);
+    };
+    let addr: IpAddr = host.trim().parse().map_err(|_| {
+        format!(
+            "Invalid bind host {:?}: expected an IP address such as 127.0.0.1 or 0.0.0.0",
+            host
+        )
+    })?;
+    if addr.is_multicast() {
         return Err(format!(
-            "Sidecar script not found at {}",
-            script_path.display()
+            "Cannot bind the sidecar to multicast address {}",
+            addr
         ));
     }
+    Ok(addr)
+}
 
-    // Set status to Loading
-    {
-        let mut sidecar = state.sidecar.lock().map_err(|e| e.to_string())?;
-        sidecar.status = SidecarStatus::Loading;
-    }
-
-    // Spawn the Python process
-    let mut child = tokio::process::Command::new(&python_path)
-        .arg(&script_path)
-        .arg("--port")
-        .arg(port.to_string())
-        .arg("--prompt")
-        .arg(&prompt)
-        .arg("--feedback")
-        .arg(feedback.to_string())
-        .arg("--strength")
-        .arg(strength.to_string())
-        .arg("--model")
-        .arg(&model)
-        .arg("--render-size")
-        .arg(render_size.to_string())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::inherit())
-        .kill_on_drop(true)
-        .spawn()
-        .map_err(|e| format!("Failed to spawn sidecar: {}", e))?;
+/// Serialized payload for the "sidecar-exposed-warning" event.
+#[derive(Clone, serde::Serialize)]
+struct ExposedWarningPayload {
+    host: IpAddr,
+    port: u16,
+}
 
-    // Read stdout lines until READY:<port>
-    let stdout = child
-        .stdout
-        .take()
-        .ok_or("Failed to capture sidecar stdout")?;
-    let mut reader = tokio::io::BufReader::new(stdout).lines();
+/// Choose how frames are blended into the feedback loop. A running
+/// sidecar is switched live, and new starts and relaunches use the mode.
+#[tauri::command]
+pub async fn set_feedback_mode(
+    mode: FeedbackMode,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    recorded_async(&state, "set_feedback_mode", async {
+        if let Ok(addr) = ready_addr(&state) {
+            let params = ParamsRequest {
+                feedback_mode: Some(mode.as_arg()),
+                ..Default::default()
+            };
+            sidecar_client(&state, addr)?
+                .set_params(&params)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        *state.feedback_mode.lock_or_recover() = mode;
+        if let Some(launch) = state.sidecar.lock_or_recover().launch.as_mut() {
+            launch.feedback_mode = mode;
+        }
+        Ok(())
+    })
+    .await
+}
 
-    // Wait for READY signal with timeout
-    let ready_port = tokio::time::timeout(std::time::Duration::from_secs(120), async {
-        while let Ok(Some(line)) = reader.next_line().await {
-            eprintln!("[sidecar] {}", line);
-            if let Some(port_str) = line.strip_prefix("READY:") {
-                let p: u16 = port_str
-                    .parse()
-                    .map_err(|e| format!("Bad port in READY signal: {}", e))?;
-                return Ok(p);
+/// Vary strength across frames of the live loop with (frame, strength)
+/// keyframes, in ascending frame order. The frontend's loop interpolates
+/// between them; the schedule is kept here so a reloaded webview can get it
+/// back from `get_strength_schedule`. Clearing it (`None` or no keyframes)
+/// puts the static strength back on a running sidecar.
+#[tauri::command]
+pub async fn set_strength_schedule(
+    keyframes: Option<Vec<(u32, f32)>>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    recorded_async(&state, "set_strength_schedule", async {
+        let keyframes = keyframes.filter(|k| !k.is_empty());
+        let Some(keyframes) = keyframes else {
+            *state.strength_schedule.lock_or_recover() = None;
+            if let Ok(addr) = ready_addr(&state) {
+                let params = ParamsRequest {
+                    strength: Some(state.settings.lock_or_recover().strength),
+                    ..Default::default()
+                };
+                sidecar_client(&state, addr)?
+                    .set_params(&params)
+                    .await
+                    .map_err(|e| e.to_string())?;
             }
+            return Ok(());
+        };
+        for &(frame, strength) in &keyframes {
+            if !(strength.is_finite() && (0.0..=1.0).contains(&strength)) {
+                return Err(format!(
+                    "Keyframe strength must be between 0 and 1, got {} at frame {}",
+                    strength, frame
+                ));
+            }
+        }
+        if let Some(pair) = keyframes.windows(2).find(|pair| pair[0].0 >= pair[1].0) {
+            return Err(format!(
+                "Keyframe frames must be in ascending order, got {} after {}",
+                pair[1].0, pair[0].0
+            ));
         }
-        Err("Sidecar exited before sending READY signal".to_string())
+        *state.strength_schedule.lock_or_recover() = Some(keyframes);
+        Ok(())
     })
     .await
-    .map_err(|_| "Sidecar startup timed out after 120s".to_string())?
-    .map_err(|e: String| e)?;
+}
 
-    // Store child in state
+#[tauri::command]
+pub fn get_strength_schedule(state: State<'_, AppState>) -> Option<Vec<(u32, f32)>> {
+    state.strength_schedule.lock_or_recover().clone()
+}
+
+/// Hold the seed across frames for coherent animation, or let it change
+/// every frame. A running sidecar's live loop is switched too, and later
+/// starts are launched with the same setting. Returns the seed that locking
+/// holds.
+#[tauri::command]
+pub async fn set_seed_lock(locked: bool, state: State<'_, AppState>) -> Result<u64, String> {
+    recorded_async(&state, "set_seed_lock", async {
+        state.seed_locked.store(locked, Ordering::Relaxed);
+        if let Ok(addr) = ready_addr(&state) {
+            let params = ParamsRequest {
+                seed_locked: Some(locked),
+                ..Default::default()
+            };
+            sidecar_client(&state, addr)?
+                .set_params(&params)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(state.seed.load(Ordering::Relaxed))
+    })
+    .await
+}
+
+/// Check a prompt the way starts and renders do, returning it as it would
+/// be sent along with anything worth telling the user.
+#[tauri::command]
+pub fn validate_prompt(
+    prompt: String,
+    state: State<'_, AppState>,
+) -> Result<ValidatedPrompt, String> {
+    let max_chars = state.settings.lock_or_recover().max_prompt_chars;
+    prompt::validate(&prompt, max_chars)
+}
+
+/// The normalized prompt, or why it can't be used.
+fn checked_prompt(state: &AppState, prompt: &str) -> Result<String, String> {
+    let max_chars = state.settings.lock_or_recover().max_prompt_chars;
+    Ok(prompt::validate(prompt, max_chars)?.prompt)
+}
+
+/// Switch between restyling the canvas and generating from the prompt
+/// alone. Takes effect from the next frame.
+#[tauri::command]
+pub fn set_mode(mode: ProcessingMode, state: State<'_, AppState>) -> Result<(), String> {
+    *state.mode.lock_or_recover() = mode;
+    Ok(())
+}
+
+/// Arguments for relaunching the last sidecar with the app's current live
+/// state, so edits made since it started survive a crash-recovery restart.
+/// The new server gets them on its command line, before it reports READY.
+pub fn relaunch_args(state: &AppState) -> Option<LaunchArgs> {
+    let mut args = state.sidecar.lock_or_recover().launch.clone()?;
+    let prompt = state.last_prompt.lock_or_recover();
+    if !prompt.is_empty() {
+        args.prompt = prompt.clone();
+    }
+    Some(args)
+}
+
+/// The body of `start_sidecar`, shared with paths that relaunch with
+/// modified arguments.
+pub async fn start_with_args(
+    app: &AppHandle,
+    state: &AppState,
+    args: LaunchArgs,
+) -> Result<SidecarStartResult, String> {
+    let launch_settings = state.settings.lock_or_recover().launch_settings();
+    // Check if already running, and claim the startup slot so a second
+    // start can't race this one while we wait for READY.
+    let cancel = Arc::new(Notify::new());
     {
-        let mut sidecar = state.sidecar.lock().map_err(|e| e.to_string())?;
-        sidecar.child = Some(child);
-        sidecar.port = ready_port;
-        sidecar.status = SidecarStatus::Ready;
+        let mut sidecar = state.sidecar.lock_or_recover();
+        if sidecar.child.is_some() {
+            return Err("Sidecar is already running".into());
+        }
+        if sidecar.startup_cancel.is_some() {
+            return Err("Sidecar is already starting".into());
+        }
+        sidecar.startup_cancel = Some(cancel.clone());
+    }
+
+    let progress = ProgressReporter::new(app, "startup");
+    let max_restarts = state.auto_restart_attempts.load(Ordering::Relaxed);
+    let mut attempt = 0;
+    let launched = loop {
+        match launch_sidecar(app, &args, state, &cancel, &progress).await {
+            Err(LaunchError::Failed(e)) if attempt < max_restarts => {
+                attempt += 1;
+                let backoff = restart_backoff(attempt);
+                eprintln!(
+                    "[sidecar] start failed (retry {} in {:?}): {}",
+                    attempt, backoff, e
+                );
+                set_status(app, state, SidecarStatus::Restarting { attempt })?;
+                progress.report(
+                    "restarting",
+                    0.0,
+                    format!("Start failed, retrying in {}s: {}", backoff.as_secs(), e),
+                );
+                tokio::select! {
+                    _ = tokio::time::sleep(backoff) => {}
+                    _ = cancel.notified() => break Err(LaunchError::Cancelled),
+                }
+            }
+            other => break other,
+        }
+    };
+
+    // Store child in state
+    let mut sidecar = state.sidecar.lock_or_recover();
+    sidecar.startup_cancel = None;
+    match launched {
+        Ok((child, ready_port)) => {
+            sidecar.child = Some(child);
+            sidecar.host = args.bind_host;
+            sidecar.port = ready_port;
+            sidecar.status = SidecarStatus::Ready;
+            sidecar.ready = Some(ReadyInfo {
+                model: args.model.clone(),
+            });
+            if !args.bind_host.is_loopback() {
+                // No authentication: anyone who can reach the port can
+                // drive the GPU and read the canvas.
+                events::emit_sidecar(
+                    app,
+                    "sidecar-exposed-warning",
+                    ExposedWarningPayload {
+                        host: args.bind_host,
+                        port: ready_port,
+                    },
+                );
+            }
+            sidecar.launch = Some(args);
+            sidecar.launch_settings = Some(launch_settings);
+            emit_status(app, &sidecar);
+            state.runs.lock_or_recover().start();
+            state.touch_activity();
+            progress.report(
+                "ready",
+                1.0,
+                format!("Sidecar ready on port {}", ready_port),
+            );
+            Ok(SidecarStartResult {
+                port: ready_port,
+                port_cleanup: None,
+            })
+        }
+        Err(LaunchError::Cancelled) => {
+            sidecar.status = SidecarStatus::Stopped;
+            emit_status(app, &sidecar);
+            progress.report("cancelled", 1.0, "Sidecar startup cancelled");
+            Err("Sidecar startup cancelled".into())
+        }
+        Err(LaunchError::Failed(e)) => {
+            sidecar.status = SidecarStatus::Error(e.clone());
+            emit_status(app, &sidecar);
+            progress.report("failed", 1.0, e.clone());
+            Err(e)
+        }
     }
+}
 
-    Ok(SidecarStartResult { port: ready_port })
+/// Delay before restart `attempt` (1-based): 1s, 2s, 4s, ... capped at 32s.
+fn restart_backoff(attempt: u32) -> std::time::Duration {
+    std::time::Duration::from_secs(1 << attempt.saturating_sub(1).min(5))
 }
 
+/// Configure how many times a failed start is retried, with backoff,
+/// before giving up. 0 disables auto-restart.
 #[tauri::command]
-pub async fn stop_sidecar(state: State<'_, AppState>) -> Result<(), String> {
-    // Take the child out of the mutex before awaiting kill, to avoid
-    // holding the lock across an await point.
-    let mut child = {
-        let mut sidecar = state.sidecar.lock().map_err(|e| e.to_string())?;
-        sidecar.status = SidecarStatus::Stopped;
-        sidecar.child.take()
+pub fn set_auto_restart(max_attempts: u32, state: State<'_, AppState>) {
+    state
+        .auto_restart_attempts
+        .store(max_attempts, Ordering::Relaxed);
+}
+
+enum LaunchError {
+    Cancelled,
+    Failed(String),
+}
+
+impl From<String> for LaunchError {
+    fn from(e: String) -> Self {
+        LaunchError::Failed(e)
+    }
+}
+
+impl From<SidecarError> for LaunchError {
+    fn from(e: SidecarError) -> Self {
+        LaunchError::Failed(e.to_string())
+    }
+}
+
+/// Poll until `port` can be bound, giving the OS time to release the socket
+/// of a killed process.
+async fn wait_for_port(
+    host: IpAddr,
+    port: u16,
+    timeout: std::time::Duration,
+) -> Result<(), SidecarError> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if std::net::TcpListener::bind((host, port)).is_ok() {
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            return Err(SidecarError::PortInUse {
+                port,
+                holder: port_holder(port),
+            });
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(25)).await;
+    }
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct PortProcess {
+    pub pid: u32,
+    pub name: String,
+}
+
+impl std::fmt::Display for PortProcess {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (pid {})", self.name, self.pid)
+    }
+}
+
+/// The first process listening on `port`, described for an error message.
+fn port_holder(port: u16) -> Option<String> {
+    port_processes(port)
+        .ok()
+        .and_then(|procs| procs.into_iter().next())
+        .map(|p| p.to_string())
+}
+
+/// Processes listening on `port`, ordered by pid.
+fn port_processes(port: u16) -> Result<Vec<PortProcess>, String> {
+    let mut procs: Vec<PortProcess> = listeners::get_processes_by_port(port)
+        .map_err(|e| format!("Failed to list listeners on port {}: {}", port, e))?
+        .into_iter()
+        .map(|p| PortProcess {
+            pid: p.pid,
+            name: p.name,
+        })
+        .collect();
+    procs.sort_by_key(|p| p.pid);
+    Ok(procs)
+}
+
+/// Kill every process listening on `port`, other than this one. Quietly
+/// does nothing when there are none or they can't be listed. Works the
+/// same on macOS, Linux, and Windows.
+fn kill_process_on_port(port: u16) {
+    let Ok(procs) = port_processes(port) else {
+        return;
     };
-    if let Some(ref mut child) = child {
-        // kill() sends SIGKILL and waits for exit, ensuring the port is
-        // actually released before we return.
-        let _ = child.kill().await;
+    let pids = pids_to_kill(&procs, sysinfo::get_current_pid().ok());
+    if pids.is_empty() {
+        return;
+    }
+    let mut system = sysinfo::System::new();
+    system.refresh_processes(sysinfo::ProcessesToUpdate::Some(&pids), true);
+    for pid in &pids {
+        if let Some(process) = system.process(*pid) {
+            eprintln!(
+                "[sidecar] killing {} (pid {}) on port {}",
+                process.name().to_string_lossy(),
+                pid,
+                port
+            );
+            process.kill();
+        }
     }
-    Ok(())
 }
 
-#[derive(serde::Serialize)]
-pub struct SidecarStatusResponse {
-    pub status: SidecarStatus,
-    pub port: u16,
+/// The pids of `procs` other than `own_pid`, once each: a process listening
+/// on both IPv4 and IPv6 is listed twice.
+fn pids_to_kill(procs: &[PortProcess], own_pid: Option<sysinfo::Pid>) -> Vec<sysinfo::Pid> {
+    let mut pids: Vec<sysinfo::Pid> = procs
+        .iter()
+        .map(|p| sysinfo::Pid::from_u32(p.pid))
+        .filter(|pid| Some(*pid) != own_pid)
+        .collect();
+    pids.sort_unstable();
+    pids.dedup();
+    pids
 }
 
+/// List the processes bound to `port`, e.g. to tell the user another app
+/// is holding the sidecar port.
 #[tauri::command]
-pub fn get_sidecar_status(state: State<'_, AppState>) -> Result<SidecarStatusResponse, String> {
-    let sidecar = state.sidecar.lock().map_err(|e| e.to_string())?;
-    Ok(SidecarStatusResponse {
-        status: sidecar.status.clone(),
-        port: sidecar.port,
-    })
+pub fn who_has_port(port: u16, state: State<'_, AppState>) -> Result<Vec<PortProcess>, String> {
+    recorded(&state, "who_has_port", || port_processes(port))
 }
 
-#[tauri::command]
-pub fn save_bytes_to_file(path: String, data: Vec<u8>) -> Result<(), String> {
-    std::fs::write(&path, &data).map_err(|e| e.to_string())
+/// Render size cap in safe mode, where inference runs on the CPU.
+const SAFE_MODE_RENDER_SIZE: u16 = 256;
+
+/// Serialized payload for the "sidecar-message" event, sent for ERROR and
+/// WARN lines during startup.
+#[derive(Clone, serde::Serialize)]
+struct SidecarMessagePayload {
+    /// "error" or "warn".
+    level: &'static str,
+    message: String,
+}
+
+/// Keep a METRIC report for `get_sidecar_metrics` and forward it to the
+/// frontend as a "sidecar-metric" event.
+fn record_metric(app: &AppHandle, metric: Metric) {
+    let state = app.state::<AppState>();
+    let mut metrics = state.metrics.lock_or_recover();
+    if metrics.len() == MAX_METRICS {
+        metrics.pop_front();
+    }
+    metrics.push_back(metric.clone());
+    drop(metrics);
+    events::emit_sidecar(app, "sidecar-metric", metric);
+}
+
+/// The most recent metrics the sidecar reported, oldest first.
+#[tauri::command]
+pub fn get_sidecar_metrics(state: State<'_, AppState>) -> Result<Vec<Metric>, String> {
+    Ok(state.metrics.lock_or_recover().iter().cloned().collect())
+}
+
+/// Whether a start kills whatever holds `port` before binding it. Port 0
+/// asks the OS for a free port, so there is nothing to clean up; the
+/// reservation taken in preflight says which one it chose.
+fn start_frees_port(port: u16) -> bool {
+    port != 0
+}
+
+/// Kill whatever still holds `args.port` (e.g. an orphan from a crash or
+/// unclean shutdown) and wait for the OS to release it.
+async fn free_port(
+    args: &LaunchArgs,
+    state: &AppState,
+    progress: &ProgressReporter,
+) -> Result<(), SidecarError> {
+    let port = args.port;
+    progress.report("cleanup", 0.0, format!("Freeing port {}", port));
+
+    // Safety net: kill any orphaned process on the target port.
+    kill_process_on_port(port);
+    let release_timeout =
+        std::time::Duration::from_millis(state.port_release_ms.load(Ordering::Relaxed));
+    wait_for_port(args.bind_host, port, release_timeout).await
+}
+
+/// Spawn the sidecar and wait for its READY line. Returns early, with the
+/// child killed, if `cancel` is notified.
+async fn launch_sidecar(
+    app: &AppHandle,
+    args: &LaunchArgs,
+    state: &AppState,
+    cancel: &Notify,
+    progress: &ProgressReporter,
+) -> Result<(tokio::process::Child, u16), LaunchError> {
+    if start_frees_port(args.port) {
+        free_port(args, state, progress).await?;
+    }
+
+    progress.report("preflight", 0.1, "Checking sidecar environment");
+    let SidecarPaths {
+        python_path,
+        script_path,
+        port_reservation,
+    } = preflight(
+        app,
+        args.bind_host,
+        args.port,
+        args.script.as_deref(),
+        args.python_path.as_deref(),
+    )
+    .await?;
+    let port = port_reservation
+        .local_addr()
+        .map_err(|e| format!("Cannot read the reserved port: {}", e))?
+        .port();
+
+    set_status(app, state, SidecarStatus::Loading)?;
+
+    // Spawn the Python process
+    progress.report("spawning", 0.2, "Starting Python");
+    let render_size = if args.safe_mode {
+        args.render_size.min(SAFE_MODE_RENDER_SIZE)
+    } else {
+        args.render_size
+    };
+    let render_size = low_power::render_size(state, render_size);
+    let mut argv = vec![
+        script_path.display().to_string(),
+        "--host".to_string(),
+        args.bind_host.to_string(),
+        "--port".to_string(),
+        port.to_string(),
+        // The prompt follows on stdin, which keeps it out of process
+        // listings and clear of argv quoting and length limits.
+        "--prompt-stdin".to_string(),
+        "--feedback".to_string(),
+        args.feedback.to_string(),
+        "--feedback-mode".to_string(),
+        args.feedback_mode.as_arg().to_string(),
+        "--strength".to_string(),
+        args.strength.to_string(),
+        "--model".to_string(),
+        args.model.clone(),
+        "--render-size".to_string(),
+        render_size.to_string(),
+    ];
+    if args.safe_mode {
+        argv.extend(["--device".to_string(), "cpu".to_string()]);
+    }
+    if !state.seed_locked.load(Ordering::Relaxed) {
+        argv.push("--unlock-seed".to_string());
+    }
+    if let Some((width, height)) = args.canvas_size {
+        argv.extend([
+            "--width".to_string(),
+            width.to_string(),
+            "--height".to_string(),
+            height.to_string(),
+        ]);
+    }
+    let model_cache = state.settings.lock_or_recover().model_cache.clone();
+    if let Some(dir) = &model_cache {
+        argv.extend(["--model-cache".to_string(), dir.display().to_string()]);
+    }
+    let command_line = cmdline::describe(&python_path, &argv);
+    eprintln!("[sidecar] spawning: {}", command_line);
+    state
+        .logs
+        .lock_or_recover()
+        .push("app", &format!("spawning: {}", command_line));
+    state.sidecar.lock_or_recover().last_command = command_line.clone();
+
+    let mut command = tokio::process::Command::new(&python_path);
+    if let Some(dir) = &model_cache {
+        command.env("HF_HOME", dir);
+    }
+    // Release the reservation at the last moment. The sidecar can't adopt
+    // our socket, so a small window remains between this drop and its own
+    // bind; a process that grabs the port in it makes the start fail
+    // cleanly rather than talk to the wrong server.
+    drop(port_reservation);
+    let mut child = command
+        .args(&argv)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("Failed to spawn sidecar: {}\nCommand: {}", e, command_line))?;
+
+    // The server reads stdin to EOF, so it is closed once the prompt is in.
+    let mut stdin = child
+        .stdin
+        .take()
+        .ok_or("Failed to open sidecar stdin".to_string())?;
+    stdin
+        .write_all(args.prompt.as_bytes())
+        .await
+        .map_err(|e| format!("Failed to send the prompt to the sidecar: {}", e))?;
+    drop(stdin);
+
+    let stderr = child
+        .stderr
+        .take()
+        .ok_or("Failed to capture sidecar stderr".to_string())?;
+    let stderr = capture_stderr(app, stderr, state.logs.clone());
+
+    // Read stdout lines until READY:<port>
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or("Failed to capture sidecar stdout".to_string())?;
+    let mut reader = tokio::io::BufReader::new(stdout).lines();
+
+    // Wait for READY signal with timeout, or for cancel_startup. Ok(None)
+    // means stdout closed first.
+    let startup_timeout = state.settings.lock_or_recover().startup_timeout_secs;
+    let read_ready = async {
+        let mut parser = LineParser::new();
+        while let Ok(Some(line)) = reader.next_line().await {
+            eprintln!("[sidecar] {}", line);
+            state.logs.lock_or_recover().push("stdout", &line);
+            match parser.feed(&line)? {
+                SidecarLine::Loading => {
+                    progress.report("loading_model", 0.3, format!("Loading {}", args.model));
+                }
+                SidecarLine::Ready { port } => return Ok(Some(port)),
+                SidecarLine::Progress { fraction, message } => {
+                    // Sidecar progress covers the model load, which owns
+                    // 0.3..0.9 of startup.
+                    let message = if message.is_empty() {
+                        format!("Loading {}", args.model)
+                    } else {
+                        message
+                    };
+                    progress.report("loading_model", 0.3 + 0.6 * fraction, message);
+                }
+                SidecarLine::Error(message) => {
+                    events::emit_sidecar(
+                        app,
+                        "sidecar-message",
+                        SidecarMessagePayload {
+                            level: "error",
+                            message,
+                        },
+                    );
+                }
+                SidecarLine::Warn(message) => {
+                    events::emit_sidecar(
+                        app,
+                        "sidecar-message",
+                        SidecarMessagePayload {
+                            level: "warn",
+                            message,
+                        },
+                    );
+                }
+                SidecarLine::Metric(metric) => record_metric(app, metric),
+                // Already in the log buffer above.
+                SidecarLine::Unrecognized(_) => {}
+            }
+        }
+        Ok::<_, String>(None)
+    };
+    // A sidecar whose stdout is buffered or redirected may never deliver
+    // READY, so answering a versioned health check counts too. A bare
+    // connect doesn't: another process may hold the port.
+    let wait_ready = tokio::time::timeout(std::time::Duration::from_secs(startup_timeout), async {
+        tokio::select! {
+            result = read_ready => result,
+            port = wait_for_handshake(state, args.bind_host, port) => Ok(Some(port)),
+        }
+    });
+    let ready_port = tokio::select! {
+        result = wait_ready => result
+            .map_err(|_| format!("Sidecar startup timed out after {}s", startup_timeout))??,
+        _ = cancel.notified() => {
+            // kill() waits for exit, so the port is free once we return.
+            let _ = child.kill().await;
+            return Err(LaunchError::Cancelled);
+        }
+    };
+
+    match ready_port {
+        Some(ready_port) => {
+            // Keep the tail reachable for the crash watchdog.
+            state.sidecar.lock_or_recover().stderr_tail = Some(stderr.tail);
+            // Keep draining stdout: if the handshake won, READY may not
+            // have been written yet, and writing it to a closed pipe would
+            // kill the sidecar.
+            let logs = state.logs.clone();
+            tauri::async_runtime::spawn(async move {
+                while let Ok(Some(line)) = reader.next_line().await {
+                    eprintln!("[sidecar] {}", line);
+                    logs.lock_or_recover().push("stdout", &line);
+                }
+            });
+            Ok((child, ready_port))
+        }
+        None => Err(describe_early_exit(&mut child, stderr).await.into()),
+    }
+}
+
+/// How often startup checks whether the sidecar has started listening.
+const READY_PROBE_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Resolves with `port` once the sidecar on it completes the control API
+/// handshake. The server only binds after its model has loaded, so this
+/// means ready.
+async fn wait_for_handshake(state: &AppState, host: IpAddr, port: u16) -> u16 {
+    let client = SidecarClient::new(
+        sidecar_client::connect_addr(host, port),
+        state.settings.lock_or_recover().client.clone(),
+    );
+    loop {
+        tokio::time::sleep(READY_PROBE_INTERVAL).await;
+        if client.handshake(READY_PROBE_INTERVAL).await {
+            return port;
+        }
+    }
+}
+
+/// Name of the sidecar entry point inside a sidecar directory.
+const SIDECAR_SCRIPT: &str = "diffusion_server.py";
+
+/// Where the sidecar may live, in order of preference: bundled resources
+/// for installed builds, then the source tree for dev builds.
+fn sidecar_dir_candidates(app: &AppHandle) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Ok(resources) = app.path().resource_dir() {
+        dirs.push(resources.join("sidecar"));
+    }
+    dirs.push(
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .parent()
+            .unwrap()
+            .join("sidecar"),
+    );
+    dirs
+}
+
+/// The first candidate directory that contains `script`, or
+/// `ScriptNotFound` listing every path checked.
+fn find_sidecar_dir(app: &AppHandle, script: &Path) -> Result<PathBuf, SidecarError> {
+    let candidates = sidecar_dir_candidates(app);
+    candidates
+        .iter()
+        .find(|dir| dir.join(script).exists())
+        .cloned()
+        .ok_or_else(|| SidecarError::ScriptNotFound {
+            checked: candidates.iter().map(|d| d.join(script)).collect(),
+        })
+}
+
+/// The sidecar directory for non-launch uses like model scanning; falls
+/// back to the last candidate when the script is missing everywhere.
+fn sidecar_dir(app: &AppHandle) -> PathBuf {
+    find_sidecar_dir(app, Path::new(SIDECAR_SCRIPT))
+        .unwrap_or_else(|_| sidecar_dir_candidates(app).pop().unwrap())
+}
+
+/// The sidecar's own interpreter, relative to its directory.
+const VENV_PYTHON: &str = ".venv/bin/python3";
+
+/// Python dependencies, relative to the sidecar directory.
+const REQUIREMENTS: &str = "requirements.txt";
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct SetupInstructions {
+    pub sidecar_dir: String,
+    /// The interpreter a start will look for: `python_path` from settings,
+    /// or the sidecar's `.venv`.
+    pub python_path: String,
+    pub python_found: bool,
+    pub requirements: String,
+    /// Shell command that creates the venv and installs the requirements,
+    /// for the user to copy; `run_sidecar_setup` does the same.
+    pub command: String,
+}
+
+/// Where the sidecar's Python environment is expected and how to create
+/// it, for a setup panel when a start fails for lack of a venv.
+#[tauri::command]
+pub fn get_setup_instructions(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<SetupInstructions, String> {
+    let dir = sidecar_dir(&app);
+    let python_path = state
+        .settings
+        .lock_or_recover()
+        .python_path
+        .clone()
+        .unwrap_or_else(|| dir.join(VENV_PYTHON));
+    let command = format!(
+        "cd {} && python3 -m venv .venv && .venv/bin/python3 -m pip install -r {}",
+        cmdline::quote(&dir.display().to_string()),
+        REQUIREMENTS
+    );
+    Ok(SetupInstructions {
+        sidecar_dir: dir.display().to_string(),
+        python_found: python_path.exists(),
+        python_path: python_path.display().to_string(),
+        requirements: dir.join(REQUIREMENTS).display().to_string(),
+        command,
+    })
+}
+
+/// Create the sidecar's venv (unless it exists) and install its
+/// requirements, reporting pip's output through "progress".
+#[tauri::command]
+pub async fn run_sidecar_setup(app: AppHandle, state: State<'_, AppState>) -> Result<(), String> {
+    recorded_async(&state, "run_sidecar_setup", async {
+        let dir = sidecar_dir(&app);
+        let progress = ProgressReporter::new(&app, "setup");
+        let result = async {
+            if !dir.join(VENV_PYTHON).exists() {
+                progress.report("venv", 0.1, "Creating the Python environment");
+                run_setup_step(
+                    &progress,
+                    "venv",
+                    0.1,
+                    Path::new("python3"),
+                    &["-m", "venv", ".venv"],
+                    &dir,
+                )
+                .await?;
+            }
+            progress.report("install", 0.3, "Installing requirements");
+            run_setup_step(
+                &progress,
+                "install",
+                0.5,
+                &dir.join(VENV_PYTHON),
+                &["-m", "pip", "install", "-r", REQUIREMENTS],
+                &dir,
+            )
+            .await
+        }
+        .await;
+        match &result {
+            Ok(()) => progress.report("done", 1.0, "Sidecar setup complete"),
+            Err(e) => progress.report("failed", 1.0, e.clone()),
+        }
+        result
+    })
+    .await
+}
+
+/// Run one setup step in `dir`, reporting each line it prints.
+async fn run_setup_step(
+    progress: &ProgressReporter,
+    phase: &str,
+    fraction: f32,
+    program: &Path,
+    args: &[&str],
+    dir: &Path,
+) -> Result<(), String> {
+    let mut child = tokio::process::Command::new(program)
+        .args(args)
+        .current_dir(dir)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("Failed to run {}: {}", program.display(), e))?;
+    // Drained on its own task so a chatty stderr can't block stdout.
+    let stderr = child
+        .stderr
+        .take()
+        .ok_or("Failed to capture setup stderr")?;
+    let last_error = tokio::spawn(async move {
+        let mut lines = tokio::io::BufReader::new(stderr).lines();
+        let mut last = None;
+        while let Ok(Some(line)) = lines.next_line().await {
+            eprintln!("[setup] {}", line);
+            last = Some(line);
+        }
+        last
+    });
+    if let Some(stdout) = child.stdout.take() {
+        let mut lines = tokio::io::BufReader::new(stdout).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            eprintln!("[setup] {}", line);
+            progress.report(phase, fraction, line);
+        }
+    }
+    let status = child.wait().await.map_err(|e| e.to_string())?;
+    if !status.success() {
+        let last_error = last_error.await.ok().flatten();
+        return Err(format!(
+            "{} {} failed: {}",
+            program.display(),
+            args.join(" "),
+            last_error.as_deref().unwrap_or("no output")
+        ));
+    }
+    Ok(())
+}
+
+/// Serialized payload for the "sidecar-missing" event.
+#[derive(Clone, serde::Serialize)]
+struct SidecarMissingPayload {
+    checked: Vec<String>,
+}
+
+struct SidecarPaths {
+    python_path: PathBuf,
+    script_path: PathBuf,
+    /// Holds the port from the free check until just before spawn, so
+    /// nothing else can grab it in between.
+    port_reservation: std::net::TcpListener,
+}
+
+/// Oldest Python the sidecar supports.
+const MIN_PYTHON: (u32, u32) = (3, 9);
+
+/// Checks shared by a real start and `validate_only`, so validation can't
+/// diverge from what a start actually requires.
+async fn preflight(
+    app: &AppHandle,
+    host: IpAddr,
+    port: u16,
+    script: Option<&str>,
+    python: Option<&Path>,
+) -> Result<SidecarPaths, SidecarError> {
+    let (configured_python, allow_external, model_cache) = {
+        let state = app.state::<AppState>();
+        let settings = state.settings.lock_or_recover();
+        (
+            python
+                .map(Path::to_path_buf)
+                .or_else(|| settings.python_path.clone()),
+            settings.allow_external_scripts,
+            settings.model_cache.clone(),
+        )
+    };
+    if let Some(dir) = model_cache.filter(|dir| !dir.is_dir()) {
+        return Err(format!("Model cache {} is not a directory", dir.display()).into());
+    }
+
+    // Determine paths. A custom script is looked up like the default one,
+    // unless it is an absolute path (and config allows that).
+    let custom = script;
+    let script = Path::new(script.unwrap_or(SIDECAR_SCRIPT));
+    if script.is_absolute() {
+        if !allow_external {
+            return Err(
+                "Absolute sidecar script paths are disabled; enable allow_external_scripts".into(),
+            );
+        }
+        if !script.is_file() {
+            return Err(SidecarError::ScriptNotFound {
+                checked: vec![script.to_path_buf()],
+            });
+        }
+    } else if !script
+        .components()
+        .all(|c| matches!(c, std::path::Component::Normal(_)))
+    {
+        return Err(format!(
+            "Sidecar script {} must stay within the sidecar directory",
+            script.display()
+        )
+        .into());
+    }
+    let found = if script.is_absolute() {
+        Ok(sidecar_dir(app))
+    } else {
+        find_sidecar_dir(app, script)
+    };
+    // Only a missing default script means a broken install; a mistyped
+    // custom one is just an error.
+    let sidecar_dir = found.inspect_err(|e| {
+        if let (SidecarError::ScriptNotFound { checked }, None) = (e, custom) {
+            events::emit_sidecar(
+                app,
+                "sidecar-missing",
+                SidecarMissingPayload {
+                    checked: checked.iter().map(|p| p.display().to_string()).collect(),
+                },
+            );
+        }
+    })?;
+    let script_path = sidecar_dir.join(script);
+    let python_path = match configured_python {
+        Some(python) if !python.is_file() => {
+            return Err(format!("Python not found at {}", python.display()).into());
+        }
+        Some(python) => python,
+        None => sidecar_dir.join(VENV_PYTHON),
+    };
+    if !python_path.exists() {
+        return Err(format!(
+            "Python venv not found at {}. Run the sidecar setup first.",
+            python_path.display()
+        )
+        .into());
+    }
+
+    check_python_version(&python_path).await?;
+
+    let port_reservation = std::net::TcpListener::bind((host, port)).map_err(|e| {
+        if e.kind() == std::io::ErrorKind::AddrInUse {
+            SidecarError::PortInUse {
+                port,
+                holder: port_holder(port),
+            }
+        } else {
+            format!("Cannot bind {}: {}", SocketAddr::new(host, port), e).into()
+        }
+    })?;
+
+    Ok(SidecarPaths {
+        python_path,
+        script_path,
+        port_reservation,
+    })
+}
+
+async fn check_python_version(python_path: &Path) -> Result<(), String> {
+    let output = tokio::process::Command::new(python_path)
+        .arg("--version")
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run {}: {}", python_path.display(), e))?;
+    // Older Pythons print the version to stderr.
+    let text = String::from_utf8_lossy(if output.stdout.is_empty() {
+        &output.stderr
+    } else {
+        &output.stdout
+    })
+    .trim()
+    .to_string();
+
+    let version = text
+        .strip_prefix("Python ")
+        .and_then(|v| {
+            let mut parts = v.split('.').map(|p| p.parse::<u32>().ok());
+            Some((parts.next()??, parts.next()??))
+        })
+        .ok_or_else(|| format!("Unrecognized Python version output: {:?}", text))?;
+
+    if version < MIN_PYTHON {
+        return Err(format!(
+            "{} is too old; the sidecar needs Python {}.{}+",
+            text, MIN_PYTHON.0, MIN_PYTHON.1
+        ));
+    }
+    Ok(())
+}
+
+/// Number of trailing stderr lines kept for startup error messages.
+const STDERR_TAIL_LINES: usize = 20;
+
+struct StderrCapture {
+    tail: Arc<Mutex<VecDeque<String>>>,
+    task: tauri::async_runtime::JoinHandle<()>,
+}
+
+/// Drain the child's stderr for its whole lifetime, echoing it to our own
+/// stderr and the log buffer and keeping the last few lines. Draining also
+/// keeps a chatty sidecar from blocking on a full pipe. METRIC lines, which
+/// arrive here once the sidecar is running, are recorded as metrics. The
+/// pipe closing wakes the crash watchdog.
+fn capture_stderr(
+    app: &AppHandle,
+    stderr: tokio::process::ChildStderr,
+    logs: Arc<Mutex<LogBuffer>>,
+) -> StderrCapture {
+    let app = app.clone();
+    let tail = Arc::new(Mutex::new(VecDeque::with_capacity(STDERR_TAIL_LINES)));
+    let task_tail = tail.clone();
+    let task = tauri::async_runtime::spawn(async move {
+        let mut lines = tokio::io::BufReader::new(stderr).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            eprintln!("[sidecar] {}", line);
+            logs.lock_or_recover().push("stderr", &line);
+            if let Ok(SidecarLine::Metric(metric)) = sidecar_protocol::parse_line(&line) {
+                record_metric(&app, metric);
+            }
+            let mut tail = task_tail.lock_or_recover();
+            if tail.len() == STDERR_TAIL_LINES {
+                tail.pop_front();
+            }
+            tail.push_back(line);
+        }
+        app.state::<AppState>().sidecar_output_closed.notify_one();
+    });
+    StderrCapture { tail, task }
+}
+
+/// Build the error for a sidecar whose stdout closed before READY, e.g.
+/// "Sidecar exited with code 1 before READY: ModuleNotFoundError: ...".
+async fn describe_early_exit(child: &mut tokio::process::Child, stderr: StderrCapture) -> String {
+    // stdout closing usually means the process is on its way out, but it
+    // can also close its end of the pipe and keep running.
+    let exit = tokio::time::timeout(std::time::Duration::from_secs(2), child.wait()).await;
+    let how = match exit {
+        Ok(Ok(status)) => describe_exit_status(status),
+        Ok(Err(_)) => "exited".to_string(),
+        Err(_) => {
+            let pid = child.id();
+            // Without stdout there's no READY to wait for; don't leave it
+            // holding the port.
+            let _ = child.kill().await;
+            return match pid {
+                Some(pid) => format!("Sidecar stopped logging before READY (pid {})", pid),
+                None => "Sidecar stopped logging before READY".to_string(),
+            };
+        }
+    };
+
+    // Let the reader pick up whatever the process wrote on its way out.
+    let _ = tokio::time::timeout(std::time::Duration::from_millis(500), stderr.task).await;
+    let tail = stderr
+        .tail
+        .lock_or_recover()
+        .iter()
+        .cloned()
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    if tail.is_empty() {
+        format!("Sidecar {} before READY", how)
+    } else {
+        format!("Sidecar {} before READY: {}", how, tail)
+    }
+}
+
+pub fn describe_exit_status(status: std::process::ExitStatus) -> String {
+    if let Some(code) = status.code() {
+        return format!("exited with code {}", code);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return format!("was killed by signal {}", signal);
+        }
+    }
+    "exited".to_string()
+}
+
+/// Abort an in-progress `start_sidecar`. The pending start kills its child
+/// and returns a "startup cancelled" error.
+#[tauri::command]
+pub fn cancel_startup(state: State<'_, AppState>) -> Result<(), String> {
+    recorded(&state, "cancel_startup", || {
+        let sidecar = state.sidecar.lock_or_recover();
+        match &sidecar.startup_cancel {
+            // notify_one() stores a permit, so this works even if the start is
+            // still in its pre-spawn cleanup and not yet waiting.
+            Some(cancel) => {
+                cancel.notify_one();
+                Ok(())
+            }
+            None => Err("Sidecar is not starting".into()),
+        }
+    })
+}
+
+/// Stop the sidecar, cancelling it first if it is still starting.
+#[tauri::command]
+pub async fn stop_sidecar(app: AppHandle, state: State<'_, AppState>) -> Result<(), String> {
+    recorded_async(&state, "stop_sidecar", async {
+        cancel_pending_start(&state).await?;
+        shutdown_sidecar(&app, &state).await.map(|_| ())
+    })
+    .await
+}
+
+/// Stop the sidecar and start it again with the last launch's arguments,
+/// updated with the live prompt, feedback, and strength. Works from any
+/// status: a pending start is cancelled first, and a sidecar in `Error` is
+/// simply relaunched. Saves the frontend a stop/start pair that can race.
+#[tauri::command]
+pub async fn restart_sidecar(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<SidecarStartResult, String> {
+    recorded_async(&state, "restart_sidecar", async {
+        let args = relaunch_args(&state).ok_or("No previous launch to restart from")?;
+        cancel_pending_start(&state).await?;
+        shutdown_sidecar(&app, &state).await?;
+        start_with_args(&app, &state, args).await
+    })
+    .await
+}
+
+/// Cancel a `start_sidecar` that is still waiting for READY, and wait
+/// until it has killed its child and given up the startup slot, so the
+/// port is free and a new start won't fail with "already starting".
+async fn cancel_pending_start(state: &AppState) -> Result<(), String> {
+    let Some(cancel) = state.sidecar.lock_or_recover().startup_cancel.clone() else {
+        return Ok(());
+    };
+    cancel.notify_one();
+    let deadline = Instant::now() + GRACEFUL_STOP;
+    while state.sidecar.lock_or_recover().startup_cancel.is_some() {
+        if Instant::now() >= deadline {
+            return Err("Timed out cancelling the pending sidecar start".into());
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    Ok(())
+}
+
+/// Stop the running sidecar, if any. Returns whether there was a child to
+/// stop.
+pub async fn shutdown_sidecar(app: &AppHandle, state: &AppState) -> Result<bool, String> {
+    // Take the child out of the mutex before awaiting kill, to avoid
+    // holding the lock across an await point.
+    let mut child = {
+        let mut sidecar = state.sidecar.lock_or_recover();
+        sidecar.ready = None;
+        sidecar.stderr_tail = None;
+        sidecar.child.take()
+    };
+    set_status(app, state, SidecarStatus::Stopped)?;
+    if let Some(ref mut child) = child {
+        stop_child(child).await;
+        state.end_run(RunEnd::Stopped);
+    }
+    Ok(child.is_some())
+}
+
+/// Stop the sidecar because the app is closing, off the main thread so the
+/// UI stays responsive, then call `then` to finish closing. Returns false,
+/// without calling `then`, when there is nothing to stop and the close can
+/// go ahead right away. While a stop is underway this returns true, so
+/// repeated close requests wait for it too.
+pub fn shutdown_for_exit(app: &AppHandle, then: impl FnOnce() + Send + 'static) -> bool {
+    let state = app.state::<AppState>();
+    {
+        let sidecar = state.sidecar.lock_or_recover();
+        if sidecar.child.is_none() && sidecar.startup_cancel.is_none() {
+            return false;
+        }
+    }
+    if state.exit_shutdown.swap(true, Ordering::SeqCst) {
+        return true;
+    }
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let state = app.state::<AppState>();
+        if let Err(e) = cancel_pending_start(&state).await {
+            eprintln!("[sidecar] {}", e);
+        }
+        if let Err(e) = shutdown_sidecar(&app, &state).await {
+            eprintln!("[sidecar] stop on exit failed: {}", e);
+        }
+        state.exit_shutdown.store(false, Ordering::SeqCst);
+        then();
+    });
+    true
+}
+
+/// How long a stopping sidecar gets to exit on its own before it is killed.
+const GRACEFUL_STOP: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Ask `child` to exit with SIGTERM so it can release the GPU, and kill it
+/// if it is still running after `GRACEFUL_STOP`. Windows has no SIGTERM, so
+/// there the child is killed straight away. Either way it has exited, and
+/// its port is free, by the time this returns.
+async fn stop_child(child: &mut tokio::process::Child) {
+    if child.id().is_some_and(terminate) {
+        let deadline = Instant::now() + GRACEFUL_STOP;
+        while Instant::now() < deadline {
+            if matches!(child.try_wait(), Ok(Some(_))) {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        eprintln!(
+            "[sidecar] still running {}s after SIGTERM; killing it",
+            GRACEFUL_STOP.as_secs()
+        );
+    }
+    // kill() sends SIGKILL and waits for exit.
+    let _ = child.kill().await;
+}
+
+/// Send SIGTERM to `pid`. Returns whether the signal was sent.
+#[cfg(unix)]
+fn terminate(pid: u32) -> bool {
+    let pid = sysinfo::Pid::from_u32(pid);
+    let mut system = sysinfo::System::new();
+    system.refresh_processes(sysinfo::ProcessesToUpdate::Some(&[pid]), true);
+    system
+        .process(pid)
+        .and_then(|process| process.kill_with(sysinfo::Signal::Term))
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn terminate(_pid: u32) -> bool {
+    false
+}
+
+/// Serialized payload for the "sidecar-force-killed" event.
+#[derive(Clone, serde::Serialize)]
+struct ForceKilledPayload {
+    count: usize,
+}
+
+/// Recovery for when orphans pile up or `SidecarState` no longer matches
+/// reality: kill every Python process running this app's sidecar script,
+/// whether we started it or not, and reset the state. Returns how many
+/// processes were killed. Never called as part of a normal start or stop.
+#[tauri::command]
+pub async fn force_kill_all_sidecars(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<usize, String> {
+    recorded_async(&state, "force_kill_all_sidecars", async {
+        let custom = {
+            let mut sidecar = state.sidecar.lock_or_recover();
+            if let Some(cancel) = sidecar.startup_cancel.as_ref() {
+                cancel.notify_one();
+            }
+            sidecar.launch.take().and_then(|launch| launch.script)
+        };
+        // Resolved the way `preflight` resolves them, so only our own scripts
+        // match and not some other project's file of the same name.
+        let scripts: Vec<PathBuf> = [Some(SIDECAR_SCRIPT), custom.as_deref()]
+            .into_iter()
+            .flatten()
+            .filter_map(|script| {
+                let script = Path::new(script);
+                if script.is_absolute() {
+                    Some(script.to_path_buf())
+                } else {
+                    find_sidecar_dir(&app, script)
+                        .ok()
+                        .map(|dir| dir.join(script))
+                }
+            })
+            .filter_map(|path| path.canonicalize().ok())
+            .collect();
+        let mut count = usize::from(shutdown_sidecar(&app, &state).await?);
+
+        let own_pid = sysinfo::get_current_pid().ok();
+        let mut system = sysinfo::System::new();
+        system.refresh_processes_specifics(
+            sysinfo::ProcessesToUpdate::All,
+            true,
+            sysinfo::ProcessRefreshKind::nothing()
+                .with_cmd(sysinfo::UpdateKind::Always)
+                .with_cwd(sysinfo::UpdateKind::Always),
+        );
+        for (pid, process) in system.processes() {
+            if Some(*pid) == own_pid {
+                continue;
+            }
+            if runs_script(process.cmd(), process.cwd(), &scripts) && process.kill() {
+                count += 1;
+            }
+        }
+
+        set_status(&app, &state, SidecarStatus::Stopped)?;
+        events::emit_sidecar(&app, "sidecar-force-killed", ForceKilledPayload { count });
+        Ok(count)
+    })
+    .await
+}
+
+/// Whether `cmd` is a Python interpreter running one of `scripts` (already
+/// canonical). The script is the first argument after the interpreter that
+/// isn't an option; a relative one is resolved against the process's `cwd`.
+fn runs_script(cmd: &[std::ffi::OsString], cwd: Option<&Path>, scripts: &[PathBuf]) -> bool {
+    let Some((interpreter, args)) = cmd.split_first() else {
+        return false;
+    };
+    let is_python = Path::new(interpreter)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.to_ascii_lowercase().starts_with("python"));
+    if !is_python {
+        return false;
+    }
+    let Some(script) = args
+        .iter()
+        .map(Path::new)
+        .find(|arg| !arg.to_string_lossy().starts_with('-'))
+    else {
+        return false;
+    };
+    let script = match cwd {
+        Some(cwd) if script.is_relative() => cwd.join(script),
+        _ => script.to_path_buf(),
+    };
+    script
+        .canonicalize()
+        .is_ok_and(|script| scripts.contains(&script))
+}
+
+/// How long a start waits for the sidecar port to become free after cleanup.
+#[tauri::command]
+pub fn set_port_release_timeout(timeout_ms: u64, state: State<'_, AppState>) {
+    state.port_release_ms.store(timeout_ms, Ordering::Relaxed);
+}
+
+/// Configure the idle auto-stop. `idle_secs == 0` disables it.
+#[tauri::command]
+pub fn set_idle_timeout(idle_secs: u64, state: State<'_, AppState>) {
+    state.idle_secs.store(idle_secs, Ordering::Relaxed);
+    state.touch_activity();
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct SidecarStatusResponse {
+    pub status: SidecarStatus,
+    /// Address the sidecar is bound to.
+    pub host: IpAddr,
+    pub port: u16,
+    /// Model loaded by the running sidecar.
+    pub model: Option<String>,
+    /// The sidecar was started in CPU-only safe mode and will be slow.
+    pub safe_mode: bool,
+    /// A frame is being rendered.
+    pub busy: bool,
+    /// Whether the sidecar answered a health check; only set when
+    /// `get_sidecar_status` is asked to probe.
+    pub reachable: Option<bool>,
+}
+
+impl SidecarStatusResponse {
+    fn from_state(sidecar: &SidecarState) -> Self {
+        Self {
+            status: sidecar.status.clone(),
+            host: sidecar.host,
+            port: sidecar.port,
+            model: sidecar.ready.as_ref().map(|r| r.model.clone()),
+            safe_mode: sidecar.launch.as_ref().is_some_and(|l| l.safe_mode),
+            busy: sidecar.renders_in_flight > 0,
+            reachable: None,
+        }
+    }
+}
+
+/// How long `get_sidecar_status` waits for a probed sidecar to answer.
+const STATUS_PROBE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// The sidecar's status as last recorded. With `probe`, a running sidecar
+/// is also sent a health check and `reachable` says whether it answered.
+/// A failed probe is only reported, never applied to `status`; the health
+/// monitor owns that, and the frontend can offer a restart meanwhile.
+#[tauri::command]
+pub async fn get_sidecar_status(
+    probe: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<SidecarStatusResponse, String> {
+    recorded_async(&state, "get_sidecar_status", async {
+        let (mut response, addr) = {
+            let sidecar = state.sidecar.lock_or_recover();
+            (
+                SidecarStatusResponse::from_state(&sidecar),
+                sidecar
+                    .child
+                    .is_some()
+                    .then(|| sidecar_client::connect_addr(sidecar.host, sidecar.port)),
+            )
+        };
+        if probe.unwrap_or(false) {
+            let reachable = match addr {
+                Some(addr) => {
+                    sidecar_client(&state, addr)?
+                        .probe(STATUS_PROBE_TIMEOUT)
+                        .await
+                }
+                None => false,
+            };
+            response.reachable = Some(reachable);
+        }
+        Ok(response)
+    })
+    .await
+}
+
+/// The port the sidecar is running on, or the configured port the next
+/// start will default to when none is running.
+#[tauri::command]
+pub fn get_port(state: State<'_, AppState>) -> Result<u16, String> {
+    {
+        let sidecar = state.sidecar.lock_or_recover();
+        if sidecar.child.is_some() {
+            return Ok(sidecar.port);
+        }
+    }
+    Ok(state.settings.lock_or_recover().port)
+}
+
+/// Cheap check for polling: a sidecar process this app spawned is ready.
+#[tauri::command]
+pub fn is_sidecar_running(state: State<'_, AppState>) -> Result<bool, String> {
+    let sidecar = state.sidecar.lock_or_recover();
+    Ok(sidecar.child.is_some() && matches!(sidecar.status, SidecarStatus::Ready))
+}
+
+#[derive(serde::Serialize)]
+pub struct TabletCapabilities {
+    /// Whether native pressure capture is compiled in for this platform.
+    pub native: bool,
+    pub device_name: Option<String>,
+    pub pressure_levels: u32,
+}
+
+#[tauri::command]
+pub fn probe_tablet_capabilities(state: State<'_, AppState>) -> Result<TabletCapabilities, String> {
+    let tablet = state.tablet.lock_or_recover();
+    Ok(TabletCapabilities {
+        native: cfg!(target_os = "macos"),
+        device_name: tablet.device_name.clone(),
+        pressure_levels: tablet.pressure_levels,
+    })
+}
+
+#[tauri::command]
+pub fn check_input_monitoring_permission() -> InputMonitoringPermission {
+    permissions::check()
+}
+
+/// Ask for Input Monitoring access, opening System Settings when macOS won't
+/// prompt again. Returns the status after asking.
+#[tauri::command]
+pub fn request_input_monitoring_permission(
+    state: State<'_, AppState>,
+) -> Result<InputMonitoringPermission, String> {
+    recorded(&state, "request_input_monitoring_permission", || {
+        permissions::request()
+    })
+}
+
+#[derive(serde::Serialize)]
+pub struct BuildInfo {
+    pub version: String,
+    pub git_commit: &'static str,
+    pub tauri_version: &'static str,
+    pub os: &'static str,
+    pub arch: &'static str,
+    /// Optional platform features compiled in, e.g. "macos-tablet".
+    pub features: Vec<String>,
+}
+
+/// Environment details for bug reports.
+#[tauri::command]
+pub fn get_build_info(app: AppHandle) -> BuildInfo {
+    let mut features = Vec::new();
+    if cfg!(target_os = "macos") {
+        features.push("macos-tablet".to_string());
+    }
+    BuildInfo {
+        version: app.package_info().version.to_string(),
+        git_commit: env!("PICTAFLUX_GIT_HASH"),
+        tauri_version: tauri::VERSION,
+        os: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+        features,
+    }
+}
+
+/// Capture the main window as a PNG. Writes it to `path` and returns the
+/// path, or returns base64 PNG data when no path is given.
+#[tauri::command]
+pub async fn take_screenshot(
+    path: Option<String>,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<String, String> {
+    recorded_async(&state, "take_screenshot", async {
+        let window = app
+            .get_webview_window(events::MAIN_WINDOW)
+            .ok_or("Main window not found")?;
+        match path {
+            Some(path) => {
+                screenshot::capture(&window, Path::new(&path)).await?;
+                Ok(path)
+            }
+            None => {
+                let tmp = std::env::temp_dir()
+                    .join(format!("pictaflux-screenshot-{}.png", std::process::id()));
+                screenshot::capture(&window, &tmp).await?;
+                let bytes = std::fs::read(&tmp).map_err(|e| e.to_string());
+                let _ = std::fs::remove_file(&tmp);
+                Ok(base64::engine::general_purpose::STANDARD.encode(bytes?))
+            }
+        }
+    })
+    .await
+}
+
+/// Native capture self-test: collect tablet samples for `secs` (default 3)
+/// while the user draws, and report what arrived. Samples are still
+/// emitted as usual. Zero samples means the problem is in native capture,
+/// not the frontend.
+#[tauri::command]
+pub async fn ping_tablet(
+    secs: Option<u64>,
+    state: State<'_, AppState>,
+) -> Result<TabletProbe, String> {
+    recorded_async(&state, "ping_tablet", async {
+        {
+            let mut probe = state.tablet_probe.lock_or_recover();
+            if probe.is_some() {
+                return Err("A tablet ping is already running".into());
+            }
+            *probe = Some(TabletProbe::default());
+        }
+        tokio::time::sleep(std::time::Duration::from_secs(secs.unwrap_or(3))).await;
+        let probe = state
+            .tablet_probe
+            .lock_or_recover()
+            .take()
+            .unwrap_or_default();
+        state.logs.lock_or_recover().push(
+            "app",
+            &format!(
+                "tablet ping: {} samples, max pressure {:.3}, tilt {}",
+                probe.samples_seen, probe.max_pressure, probe.had_tilt
+            ),
+        );
+        Ok(probe)
+    })
+    .await
+}
+
+/// Tablet devices seen via proximity events this session.
+#[tauri::command]
+pub fn get_tablet_devices(state: State<'_, AppState>) -> Result<Vec<TabletDevice>, String> {
+    Ok(state.tablet.lock_or_recover().devices.clone())
+}
+
+/// Only emit tablet samples from `device_id`; `None` accepts every device.
+/// Trackpad pressure is dropped while a device is selected.
+#[tauri::command]
+pub fn set_active_tablet_device(
+    device_id: Option<u64>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    recorded(&state, "set_active_tablet_device", || {
+        let mut tablet = state.tablet.lock_or_recover();
+        if let Some(id) = device_id {
+            if !tablet.devices.iter().any(|d| d.device_id == id) {
+                return Err(format!("Unknown tablet device {}", id));
+            }
+        }
+        tablet.active_device = device_id;
+        Ok(())
+    })
+}
+
+/// Where to reach the running sidecar, or an error if it isn't `Ready`.
+fn ready_addr(state: &AppState) -> Result<SocketAddr, String> {
+    let sidecar = state.sidecar.lock_or_recover();
+    match sidecar.status {
+        SidecarStatus::Ready => Ok(sidecar_client::connect_addr(sidecar.host, sidecar.port)),
+        ref status => Err(format!("Sidecar is not ready (status: {:?})", status)),
+    }
+}
+
+/// A control API client for the sidecar at `addr`, configured from the
+/// settings.
+fn sidecar_client(state: &AppState, addr: SocketAddr) -> Result<SidecarClient, String> {
+    let config = state.settings.lock_or_recover().client.clone();
+    Ok(SidecarClient::new(addr, config))
+}
+
+/// Base URL of the running sidecar, e.g. "http://127.0.0.1:9824", for
+/// frontend code that talks to it directly.
+#[tauri::command]
+pub fn open_sidecar_url(state: State<'_, AppState>) -> Result<String, String> {
+    recorded(&state, "open_sidecar_url", || {
+        Ok(sidecar_client::base_url(ready_addr(&state)?))
+    })
+}
+
+/// Save the prompt and sidecar parameters so the next launch starts with
+/// them. Frames call this too, so nothing is written unless a value
+/// changed; a failed write is logged rather than failing the caller. The
+/// write happens after the settings lock is released, so other commands
+/// aren't held up by the disk.
+fn remember_session(
+    app: &AppHandle,
+    state: &AppState,
+    prompt: Option<&str>,
+    feedback: Option<f32>,
+    strength: Option<f32>,
+    port: Option<u16>,
+) {
+    let changed = {
+        let mut settings = state.settings.lock_or_recover();
+        settings
+            .remember_session(prompt, feedback, strength, port)
+            .then(|| settings.clone())
+    };
+    if let Some(settings) = changed {
+        if let Err(e) = config::save(app, &settings) {
+            eprintln!("[settings] failed to save the session: {}", e);
+        }
+    }
+}
+
+/// Change the running sidecar's prompt, feedback, strength, or feedback
+/// mode without the restart (and model reload) a new `start_sidecar` would
+/// take. Values sent are also kept for relaunches.
+#[tauri::command]
+pub async fn update_sidecar_params(
+    prompt: Option<String>,
+    feedback: Option<f32>,
+    strength: Option<f32>,
+    feedback_mode: Option<FeedbackMode>,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    recorded_async(&state, "update_sidecar_params", async {
+        for (name, value) in [("feedback", feedback), ("strength", strength)] {
+            if let Some(value) = value {
+                if !(value.is_finite() && (0.0..=1.0).contains(&value)) {
+                    return Err(format!("{} must be between 0 and 1, got {}", name, value));
+                }
+            }
+        }
+        let prompt = prompt
+            .map(|prompt| checked_prompt(&state, &prompt))
+            .transpose()?;
+        let addr = ready_addr(&state)?;
+        let params = ParamsRequest {
+            prompt: prompt.as_deref(),
+            feedback,
+            strength,
+            feedback_mode: feedback_mode.map(FeedbackMode::as_arg),
+            ..Default::default()
+        };
+        sidecar_client(&state, addr)?
+            .set_params(&params)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if let Some(prompt) = &prompt {
+            *state.last_prompt.lock_or_recover() = prompt.clone();
+        }
+        if let Some(mode) = feedback_mode {
+            *state.feedback_mode.lock_or_recover() = mode;
+        }
+        remember_session(&app, &state, prompt.as_deref(), feedback, strength, None);
+        if let Some(launch) = state.sidecar.lock_or_recover().launch.as_mut() {
+            if let Some(prompt) = prompt {
+                launch.prompt = prompt;
+            }
+            launch.feedback = feedback.unwrap_or(launch.feedback);
+            launch.strength = strength.unwrap_or(launch.strength);
+            launch.feedback_mode = feedback_mode.unwrap_or(launch.feedback_mode);
+        }
+        Ok(())
+    })
+    .await
+}
+
+/// Ask the sidecar to release cached GPU memory (`torch.cuda.empty_cache()`
+/// or `torch.mps.empty_cache()`). `freed_mb` is `None` when torch has no GPU
+/// backend there, e.g. a CoreML-only install.
+#[tauri::command]
+pub async fn flush_gpu_cache(state: State<'_, AppState>) -> Result<FlushCacheResponse, String> {
+    recorded_async(&state, "flush_gpu_cache", async {
+        let addr = ready_addr(&state)?;
+        sidecar_client(&state, addr)?
+            .flush_cache()
+            .await
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+#[derive(serde::Serialize)]
+pub struct SetModelCacheResult {
+    /// The running sidecar switched to the new cache without a restart.
+    pub applied: bool,
+    /// The new cache only takes effect after the sidecar restarts.
+    pub restart_required: bool,
+}
+
+/// Move the model cache (`HF_HOME`). The path is saved to `Settings` and
+/// used from the next start; a running sidecar is asked to switch live and,
+/// if it can't, is restarted or told to be (see `sync_sidecar_settings`).
+#[tauri::command]
+pub async fn set_model_cache(
+    path: String,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<SetModelCacheResult, String> {
+    recorded_async(&state, "set_model_cache", async {
+        let dir = PathBuf::from(&path);
+        if !dir.is_dir() {
+            return Err(format!("{} is not a directory", dir.display()));
+        }
+        let mut settings = state.settings.lock_or_recover().clone();
+        settings.model_cache = Some(dir);
+        store_settings(&app, &state, settings)?;
+
+        let sync = sync_sidecar_settings(&app, &state).await?;
+        Ok(SetModelCacheResult {
+            applied: sync.hot_applied.contains(&"model_cache"),
+            restart_required: sync.restart_pending.contains(&"model_cache"),
+        })
+    })
+    .await
+}
+
+/// Serialized payload for the "sidecar-restart-required" event.
+#[derive(Clone, serde::Serialize)]
+struct SidecarRestartRequiredPayload {
+    reason: String,
+    /// Settings the running sidecar hasn't picked up, e.g. "port".
+    changes: Vec<&'static str>,
+}
+
+/// What `sync_sidecar_settings` did with the changed launch settings.
+#[derive(Default)]
+struct SettingsSync {
+    /// Taken by the running sidecar without a restart.
+    hot_applied: Vec<&'static str>,
+    /// Still waiting for a restart.
+    restart_pending: Vec<&'static str>,
+}
+
+/// Bring a running sidecar in line with the settings it was started with.
+/// A moved model cache is applied live when the sidecar supports it. Any
+/// other change restarts the sidecar when `restart_on_settings_change` is
+/// set, and otherwise emits "sidecar-restart-required" listing them.
+async fn sync_sidecar_settings(app: &AppHandle, state: &AppState) -> Result<SettingsSync, String> {
+    let mut sync = SettingsSync::default();
+    let Ok(addr) = ready_addr(state) else {
+        // Nothing running; the next start picks everything up.
+        return Ok(sync);
+    };
+    let Some(mut launched) = state.sidecar.lock_or_recover().launch_settings.clone() else {
+        return Ok(sync);
+    };
+    let settings = state.settings.lock_or_recover().clone();
+    let current = settings.launch_settings();
+
+    // Clearing the cache path can't be applied live; that needs a restart.
+    let moved_cache = current
+        .model_cache
+        .as_ref()
+        .filter(|_| launched.model_cache != current.model_cache);
+    if let Some(dir) = moved_cache {
+        let applied = sidecar_client(state, addr)?
+            .set_model_cache(&dir.display().to_string())
+            .await
+            .is_ok();
+        if applied {
+            launched.model_cache = current.model_cache.clone();
+            if let Some(running) = state.sidecar.lock_or_recover().launch_settings.as_mut() {
+                running.model_cache = current.model_cache.clone();
+            }
+            sync.hot_applied.push("model_cache");
+        }
+    }
+
+    let changes = launched.changes(&current);
+    if changes.is_empty() {
+        return Ok(sync);
+    }
+    if settings.restart_on_settings_change {
+        let mut args = relaunch_args(state).ok_or("No previous launch to restart from")?;
+        args.port = settings.port;
+        shutdown_sidecar(app, state).await?;
+        start_with_args(app, state, args).await?;
+        return Ok(sync);
+    }
+    events::emit_sidecar(
+        app,
+        "sidecar-restart-required",
+        SidecarRestartRequiredPayload {
+            reason: format!("Restart to apply: {}", changes.join(", ")),
+            changes: changes.clone(),
+        },
+    );
+    sync.restart_pending = changes;
+    Ok(sync)
+}
+
+/// Models the sidecar can load: asked of the running sidecar when it is
+/// ready, otherwise scanned from the models directory.
+#[tauri::command]
+pub async fn get_available_models(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<Vec<ModelInfo>, String> {
+    recorded_async(&state, "get_available_models", async {
+        if let Some(models) = state
+            .model_cache
+            .lock_or_recover()
+            .as_ref()
+            .and_then(|cache| cache.fresh())
+        {
+            return Ok(models.to_vec());
+        }
+
+        let models = match ready_addr(&state) {
+            Ok(addr) => sidecar_client(&state, addr)?
+                .models()
+                .await
+                .map_err(|e| format!("Failed to list sidecar models: {}", e))?,
+            Err(_) => {
+                let dir = state.models_dir.lock_or_recover().clone();
+                models::scan_dir(&dir.unwrap_or_else(|| sidecar_dir(&app).join("models")))
+            }
+        };
+
+        *state.model_cache.lock_or_recover() = Some(ModelCache {
+            fetched: Instant::now(),
+            models: models.clone(),
+        });
+        Ok(models)
+    })
+    .await
+}
+
+/// Rough VRAM needed per MB of fp16 weights, covering activations and the
+/// VAE's working buffers on top of the weights themselves.
+const VRAM_PER_WEIGHT_MB: f64 = 1.5;
+
+#[derive(Clone, serde::Serialize)]
+pub struct GpuMemoryCheck {
+    /// `None` when GPU memory can't be queried on this machine.
+    pub vram_total_mb: Option<u64>,
+    pub vram_free_mb: Option<u64>,
+    /// Estimated from the model's weight size; `None` for unknown models.
+    pub model_requires_mb: Option<u64>,
+    /// False only when both sides are known and free memory falls short.
+    pub sufficient: bool,
+}
+
+/// Check free GPU memory against what `model` needs, so the UI can warn
+/// before a start that would run out of memory partway through loading.
+/// A running sidecar's own usage counts against free memory.
+#[tauri::command]
+pub async fn check_gpu_memory(
+    model: String,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<GpuMemoryCheck, String> {
+    recorded_async(&state, "check_gpu_memory", async {
+        let models = get_available_models(app, state).await?;
+        let model_requires_mb = models
+            .iter()
+            .find(|m| m.id == model)
+            .map(|m| (m.size_mb * VRAM_PER_WEIGHT_MB).ceil() as u64);
+        let memory = gpu::query().await;
+        let sufficient = match (&memory, model_requires_mb) {
+            (Some(memory), Some(required)) => memory.free_mb >= required,
+            _ => true,
+        };
+        Ok(GpuMemoryCheck {
+            vram_total_mb: memory.as_ref().map(|m| m.total_mb),
+            vram_free_mb: memory.as_ref().map(|m| m.free_mb),
+            model_requires_mb,
+            sufficient,
+        })
+    })
+    .await
+}
+
+/// Set the directory scanned by `get_available_models` while the sidecar is
+/// not running. `None` restores the default `sidecar/models`.
+#[tauri::command]
+pub fn set_models_dir(path: Option<String>, state: State<'_, AppState>) -> Result<(), String> {
+    recorded(&state, "set_models_dir", || {
+        let path = path.map(PathBuf::from);
+        if let Some(dir) = &path {
+            if !dir.is_dir() {
+                return Err(format!("Models directory not found: {}", dir.display()));
+            }
+        }
+        *state.models_dir.lock_or_recover() = path;
+        *state.model_cache.lock_or_recover() = None;
+        Ok(())
+    })
+}
+
+/// Switch the running sidecar to another model. Hot-swaps over the control
+/// port when the sidecar supports it, keeping warmup; otherwise restarts it
+/// with the new model.
+#[tauri::command]
+pub async fn switch_model(
+    model_id: String,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<SidecarStartResult, String> {
+    recorded_async(&state, "switch_model", async {
+        let addr = ready_addr(&state)?;
+        set_status(&app, &state, SidecarStatus::Loading)?;
+
+        // The response only arrives once the new model is loaded, so it doubles
+        // as the READY confirmation.
+        match sidecar_client(&state, addr)?.switch_model(&model_id).await {
+            Ok(()) => {
+                {
+                    let mut sidecar = state.sidecar.lock_or_recover();
+                    if let Some(ready) = sidecar.ready.as_mut() {
+                        ready.model = model_id.clone();
+                    }
+                    if let Some(launch) = sidecar.launch.as_mut() {
+                        launch.model = model_id;
+                    }
+                }
+                set_status(&app, &state, SidecarStatus::Ready)?;
+                Ok(SidecarStartResult {
+                    port: addr.port(),
+                    port_cleanup: None,
+                })
+            }
+            Err(ClientError::Unsupported(_)) => {
+                // No hot-swap support: restart with the new model.
+                let mut args = state
+                    .sidecar
+                    .lock_or_recover()
+                    .launch
+                    .clone()
+                    .ok_or("No previous launch to restart from")?;
+                args.model = model_id;
+                shutdown_sidecar(&app, &state).await?;
+                start_with_args(&app, &state, args).await
+            }
+            Err(e @ ClientError::Unreachable(_)) => {
+                let message = format!("Model switch failed: {}", e);
+                set_status(&app, &state, SidecarStatus::Error(message.clone()))?;
+                Err(message)
+            }
+            Err(e) => {
+                // The sidecar rejected the model but is still serving the old one.
+                let message = format!("Sidecar failed to switch model: {}", e);
+                set_status(&app, &state, SidecarStatus::Ready)?;
+                Err(message)
+            }
+        }
+    })
+    .await
+}
+
+/// The full command line (with relevant environment) of the most recent
+/// sidecar spawn, for bug reports. Empty if nothing has been spawned yet.
+#[tauri::command]
+pub fn get_last_sidecar_command(state: State<'_, AppState>) -> Result<String, String> {
+    Ok(state.sidecar.lock_or_recover().last_command.clone())
+}
+
+/// Recent sidecar output from the log buffer, oldest first.
+#[tauri::command]
+pub fn get_sidecar_logs(state: State<'_, AppState>) -> Result<Vec<LogLine>, String> {
+    Ok(state.logs.lock_or_recover().lines())
+}
+
+/// Adjust the log buffer's limits. Omitted values are left unchanged.
+#[tauri::command]
+pub fn set_log_limits(
+    max_lines: Option<usize>,
+    max_bytes: Option<usize>,
+    max_line_bytes: Option<usize>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let mut logs = state.logs.lock_or_recover();
+    if let Some(max_lines) = max_lines {
+        logs.max_lines = max_lines.max(1);
+    }
+    if let Some(max_bytes) = max_bytes {
+        logs.max_bytes = max_bytes.max(1024);
+    }
+    if let Some(max_line_bytes) = max_line_bytes {
+        logs.max_line_bytes = max_line_bytes.max(80);
+    }
+    logs.evict();
+    Ok(())
+}
+
+/// Switch between per-sample "native-tablet" events (the default) and one
+/// "native-tablet-batch" event per frame carrying every sample since the
+/// last one.
+#[tauri::command]
+pub fn set_tablet_batch_mode(enabled: bool, state: State<'_, AppState>) {
+    state.tablet_batch.store(enabled, Ordering::Relaxed);
+}
+
+/// Only emit tablet samples while the target window is focused (the
+/// default), so clicking through from another app doesn't draw.
+#[tauri::command]
+pub fn set_tablet_focus_gating(enabled: bool, state: State<'_, AppState>) {
+    state.tablet_focus_gating.store(enabled, Ordering::Relaxed);
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct CurrentPrompt {
+    pub prompt: String,
+}
+
+/// The prompt most recently sent with a frame, so a newly opened window can
+/// restore it.
+#[tauri::command]
+pub fn get_current_prompt(state: State<'_, AppState>) -> Result<CurrentPrompt, String> {
+    Ok(CurrentPrompt {
+        prompt: state.last_prompt.lock_or_recover().clone(),
+    })
+}
+
+#[tauri::command]
+pub fn get_settings(state: State<'_, AppState>) -> Result<Settings, String> {
+    Ok(state.settings.lock_or_recover().clone())
+}
+
+/// Replace the settings, validating and saving them first. A running
+/// sidecar is then brought in line in the background; settings it only
+/// reads at startup are applied live, by a restart, or reported through
+/// "sidecar-restart-required" (see `sync_sidecar_settings`).
+#[tauri::command]
+pub fn update_settings(
+    settings: Settings,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<Settings, String> {
+    recorded(&state, "update_settings", || {
+        settings.check_paths(&state.settings.lock_or_recover())?;
+        let settings = store_settings(&app, &state, settings)?;
+        tauri::async_runtime::spawn(async move {
+            let state = app.state::<AppState>();
+            if let Err(e) = sync_sidecar_settings(&app, &state).await {
+                eprintln!("[settings] failed to apply to the sidecar: {}", e);
+            }
+        });
+        Ok(settings)
+    })
+}
+
+/// Validate, save, and apply `settings` without touching the sidecar.
+fn store_settings(
+    app: &AppHandle,
+    state: &AppState,
+    settings: Settings,
+) -> Result<Settings, String> {
+    settings.validate()?;
+    config::save(app, &settings)?;
+    settings.apply(state);
+    *state.settings.lock_or_recover() = settings.clone();
+    Ok(settings)
+}
+
+#[tauri::command]
+pub fn reset_settings(app: AppHandle, state: State<'_, AppState>) -> Result<Settings, String> {
+    update_settings(Settings::default(), app, state)
+}
+
+/// Save the current smoothing and pressure curve as profile `name`. A
+/// profile named after a tablet (as listed by `get_tablet_devices`) is
+/// applied automatically whenever that tablet comes into proximity.
+#[tauri::command]
+pub fn save_pressure_profile(
+    name: String,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<Settings, String> {
+    recorded(&state, "save_pressure_profile", || {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("Profile name must not be empty".into());
+        }
+        let mut settings = state.settings.lock_or_recover().clone();
+        let profile = settings.pressure_profile();
+        settings.pressure_profiles.insert(name.to_string(), profile);
+        update_settings(settings, app, state)
+    })
+}
+
+/// Make saved profile `name` the current pen settings.
+#[tauri::command]
+pub fn load_pressure_profile(
+    name: String,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<Settings, String> {
+    recorded(&state, "load_pressure_profile", || {
+        let mut settings = state.settings.lock_or_recover().clone();
+        let profile = settings
+            .pressure_profiles
+            .get(&name)
+            .cloned()
+            .ok_or_else(|| format!("No pressure profile named {:?}", name))?;
+        settings.set_pressure_profile(&profile);
+        update_settings(settings, app, state)
+    })
+}
+
+/// Write the current smoothing and pressure curve to `path` as a versioned
+/// JSON file others can import.
+#[tauri::command]
+pub fn export_pressure_curve(path: String, state: State<'_, AppState>) -> Result<(), String> {
+    recorded(&state, "export_pressure_curve", || {
+        let profile = state.settings.lock_or_recover().pressure_profile();
+        persist::save_json(Path::new(&path), &PressureCurveFile::new(profile))
+    })
+}
+
+/// Make the pressure curve in `path` (from `export_pressure_curve`) the
+/// current pen settings, after checking its version and ranges.
+#[tauri::command]
+pub fn import_pressure_curve(
+    path: String,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<Settings, String> {
+    recorded(&state, "import_pressure_curve", || {
+        let file = PressureCurveFile::load(Path::new(&path))?;
+        let mut settings = state.settings.lock_or_recover().clone();
+        settings.set_pressure_profile(&file.profile);
+        update_settings(settings, app, state)
+    })
+}
+
+#[tauri::command]
+pub fn list_pressure_profiles(
+    state: State<'_, AppState>,
+) -> Result<BTreeMap<String, PressureProfile>, String> {
+    Ok(state.settings.lock_or_recover().pressure_profiles.clone())
+}
+
+/// Serialized payload for the "pressure-profile-applied" event.
+#[cfg(target_os = "macos")]
+#[derive(Clone, serde::Serialize)]
+struct PressureProfileAppliedPayload {
+    name: String,
+    profile: PressureProfile,
+}
+
+/// Switch to the pressure profile saved for `device_name`, if there is one.
+/// Called by the tablet monitor when a different device enters proximity,
+/// so the switch happens in memory and is saved in the background: the
+/// monitor runs on the main thread. Saved profiles were validated already,
+/// and the sidecar doesn't use them.
+#[cfg(target_os = "macos")]
+pub fn apply_device_pressure_profile(app: &AppHandle, device_name: &str) {
+    let state = app.state::<AppState>();
+    let profile = {
+        let mut settings = state.settings.lock_or_recover();
+        let Some(profile) = settings.pressure_profiles.get(device_name).cloned() else {
+            return;
+        };
+        if settings.pressure_profile() == profile {
+            return;
+        }
+        settings.set_pressure_profile(&profile);
+        profile
+    };
+    events::emit_tablet(
+        app,
+        "pressure-profile-applied",
+        PressureProfileAppliedPayload {
+            name: device_name.to_string(),
+            profile,
+        },
+    );
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        // Saves whatever is current by now, so a later change isn't
+        // overwritten by this one arriving late.
+        let settings = app.state::<AppState>().settings.lock_or_recover().clone();
+        if let Err(e) = config::save(&app, &settings) {
+            eprintln!("[settings] failed to save the pressure profile: {}", e);
+        }
+    });
+}
+
+/// Start recording tablet events and processing calls to a JSONL file.
+#[tauri::command]
+pub fn start_recording(path: String, state: State<'_, AppState>) -> Result<(), String> {
+    recorded(&state, "start_recording", || {
+        let mut recorder = state.recorder.lock_or_recover();
+        if recorder.is_some() {
+            return Err("A recording is already in progress".into());
+        }
+        *recorder = Some(Recorder::create(Path::new(&path))?);
+        Ok(())
+    })
+}
+
+/// Stop the active recording. Returns the number of events written.
+#[tauri::command]
+pub fn stop_recording(state: State<'_, AppState>) -> Result<u64, String> {
+    recorded(&state, "stop_recording", || {
+        let recorder = state
+            .recorder
+            .lock_or_recover()
+            .take()
+            .ok_or("No recording in progress")?;
+        recorder.finish()
+    })
+}
+
+/// Serialized payload for the "replay-progress" event.
+#[derive(Clone, serde::Serialize)]
+struct ReplayProgressPayload {
+    /// Events replayed so far.
+    replayed: usize,
+    total: usize,
+    /// Recording offset of the event just replayed.
+    t_ms: u64,
+}
+
+/// Re-emit one recorded event through the channel it was captured from.
+fn replay_entry(app: &AppHandle, entry: &RecordedEvent) {
+    if entry.event == "process-canvas" {
+        events::emit_sidecar(app, &entry.event, entry.payload.clone());
+    } else {
+        events::deliver_tablet(app, &entry.event, entry.payload.clone());
+    }
+}
+
+/// Replay speeds outside this range are clamped to it. Far below it the
+/// replay would take days, and dividing event times by a speed near zero
+/// overflows `Duration`.
+const REPLAY_SPEED: std::ops::RangeInclusive<f32> = 0.05..=20.0;
+
+/// Re-emit a recording's events at their original timing, or `speed` times
+/// faster (0.5 replays at half speed; clamped to `REPLAY_SPEED`). Returns the
+/// number of events replayed.
+#[tauri::command]
+pub async fn replay_recording(
+    path: String,
+    speed: Option<f32>,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<usize, String> {
+    recorded_async(&state, "replay_recording", async {
+        let speed = speed.unwrap_or(1.0);
+        if !(speed > 0.0 && speed.is_finite()) {
+            return Err(format!("speed must be positive, got {}", speed));
+        }
+        let speed = speed.clamp(*REPLAY_SPEED.start(), *REPLAY_SPEED.end());
+        let entries = recording::load(Path::new(&path))?;
+        let started = Instant::now();
+        for (i, entry) in entries.iter().enumerate() {
+            let offset = std::time::Duration::from_millis(entry.t_ms).div_f32(speed);
+            tokio::time::sleep_until((started + offset).into()).await;
+            replay_entry(&app, entry);
+            events::emit_sidecar(
+                &app,
+                "replay-progress",
+                ReplayProgressPayload {
+                    replayed: i + 1,
+                    total: entries.len(),
+                    t_ms: entry.t_ms,
+                },
+            );
+        }
+        Ok(entries.len())
+    })
+    .await
+}
+
+/// Replay the next event of the recording at `path`, ignoring its timing.
+/// Repeated calls walk through the file; switching to another path starts
+/// from its beginning. Returns the event replayed, or `None` once the
+/// recording is exhausted.
+#[tauri::command]
+pub fn replay_step(
+    path: String,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<Option<RecordedEvent>, String> {
+    recorded(&state, "replay_step", || {
+        let path = PathBuf::from(path);
+        let mut replay = state.replay.lock_or_recover();
+        if replay.as_ref().is_none_or(|cursor| cursor.path != path) {
+            *replay = Some(ReplayCursor::open(&path)?);
+        }
+        let Some(cursor) = replay.as_mut() else {
+            return Ok(None);
+        };
+        let total = cursor.entries.len();
+        let Some(entry) = cursor.advance().cloned() else {
+            *replay = None;
+            return Ok(None);
+        };
+        replay_entry(&app, &entry);
+        events::emit_sidecar(
+            &app,
+            "replay-progress",
+            ReplayProgressPayload {
+                replayed: cursor.next,
+                total,
+                t_ms: entry.t_ms,
+            },
+        );
+        Ok(Some(entry))
+    })
+}
+
+/// Recovery escape hatch after a failure: abort any pending start, kill any
+/// lingering child, and return the sidecar state to its launch defaults,
+/// dropping the failed run's error and stderr tail.
+#[tauri::command]
+pub async fn reset_sidecar_state(app: AppHandle, state: State<'_, AppState>) -> Result<(), String> {
+    recorded_async(&state, "reset_sidecar_state", async {
+        cancel_pending_start(&state).await?;
+        shutdown_sidecar(&app, &state).await?;
+
+        let mut sidecar = state.sidecar.lock_or_recover();
+        sidecar.host = DEFAULT_BIND_HOST;
+        sidecar.port = DEFAULT_PORT;
+        sidecar.ready = None;
+        sidecar.stderr_tail = None;
+        sidecar.status = SidecarStatus::Stopped;
+        emit_status(&app, &sidecar);
+        Ok(())
+    })
+    .await
+}
+
+/// Set the sidecar status and emit "sidecar-status-changed". Every status
+/// change goes through here or `emit_status`, so the frontend can follow
+/// the sidecar without polling `get_sidecar_status`.
+pub fn set_status(app: &AppHandle, state: &AppState, status: SidecarStatus) -> Result<(), String> {
+    let mut sidecar = state.sidecar.lock_or_recover();
+    sidecar.status = status;
+    emit_status(app, &sidecar);
+    Ok(())
+}
+
+/// Emit "sidecar-status-changed" for a status already set under the lock,
+/// with the rest of the state that changed alongside it.
+fn emit_status(app: &AppHandle, sidecar: &SidecarState) {
+    events::emit_sidecar(
+        app,
+        "sidecar-status-changed",
+        SidecarStatusResponse::from_state(sidecar),
+    );
+}
+
+/// Choose which window receives native tablet events.
+#[tauri::command]
+pub fn set_tablet_target_window(
+    label: String,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    recorded(&state, "set_tablet_target_window", || {
+        app.get_webview_window(&label)
+            .ok_or_else(|| format!("No window labeled {:?}", label))?;
+        *state.tablet_target.lock_or_recover() = label;
+        Ok(())
+    })
+}
+
+/// Serialized payload for the "scale-factor-changed" event.
+#[derive(Clone, serde::Serialize)]
+struct ScaleFactorChangedPayload {
+    scale_factor: f64,
+}
+
+/// Tell the frontend the tablet target window moved to a display with a
+/// different scale factor. Tablet positions already follow it, since they
+/// are converted with each event's window's own scale.
+pub fn scale_factor_changed(window: &tauri::Window, scale_factor: f64) {
+    let state = window.state::<AppState>();
+    let is_target = *state.tablet_target.lock_or_recover() == window.label();
+    if !is_target {
+        return;
+    }
+    events::emit_tablet(
+        window.app_handle(),
+        "scale-factor-changed",
+        ScaleFactorChangedPayload { scale_factor },
+    );
+}
+
+/// Default frame rate of videos assembled by `stop_loop_capture`.
+const LOOP_CAPTURE_FPS: u32 = 24;
+
+/// Serialized payload for the "loop-capture-progress" event.
+#[derive(Clone, serde::Serialize)]
+struct LoopCaptureProgressPayload {
+    frames: u64,
+}
+
+/// Start saving live-loop frames to `output_dir` as numbered PNGs.
+#[tauri::command]
+pub fn start_loop_capture(output_dir: String, state: State<'_, AppState>) -> Result<(), String> {
+    recorded(&state, "start_loop_capture", || {
+        let mut capture = state.loop_capture.lock_or_recover();
+        if capture.is_some() {
+            return Err("A loop capture is already in progress".into());
+        }
+        *capture = Some(LoopCapture::create(Path::new(&output_dir))?);
+        power::hold_for_run(&state, power::LOOP_CAPTURE);
+        Ok(())
+    })
+}
+
+/// Save one frame of the live loop, as received from the sidecar. Returns
+/// the number of frames captured so far.
+#[tauri::command]
+pub async fn capture_loop_frame(
+    data: Vec<u8>,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<u64, String> {
+    recorded_async(&state, "capture_loop_frame", async {
+        // Claim the frame number under the lock, but convert and write outside
+        // it so the next frame doesn't wait on this one.
+        let (path, frames) = {
+            let mut capture = state.loop_capture.lock_or_recover();
+            let capture = capture.as_mut().ok_or("No loop capture in progress")?;
+            (capture.claim_frame(), capture.frames)
+        };
+        loop_capture::write_frame(&path, &data)?;
+        events::emit_sidecar(
+            &app,
+            "loop-capture-progress",
+            LoopCaptureProgressPayload { frames },
+        );
+        Ok(frames)
+    })
+    .await
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct LoopCaptureSummary {
+    pub frames: u64,
+    pub output_dir: String,
+    /// The assembled mp4, when one was requested.
+    pub video: Option<String>,
+}
+
+/// Stop the loop capture. With `make_video`, ffmpeg assembles the frames
+/// into `loop.mp4` in the same directory at `fps` (default 24).
+#[tauri::command]
+pub async fn stop_loop_capture(
+    make_video: Option<bool>,
+    fps: Option<u32>,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<LoopCaptureSummary, String> {
+    recorded_async(&state, "stop_loop_capture", async {
+        let capture = state
+            .loop_capture
+            .lock_or_recover()
+            .take()
+            .ok_or("No loop capture in progress")?;
+        power::release_for_run(&state, power::LOOP_CAPTURE);
+        let mut summary = LoopCaptureSummary {
+            frames: capture.frames,
+            output_dir: capture.dir.display().to_string(),
+            video: None,
+        };
+        if !make_video.unwrap_or(false) || capture.frames == 0 {
+            return Ok(summary);
+        }
+
+        let video = capture.dir.join("loop.mp4");
+        let args =
+            loop_capture::ffmpeg_args(&capture.dir, fps.unwrap_or(LOOP_CAPTURE_FPS).max(1), &video);
+        let output = app
+            .shell()
+            .command("ffmpeg")
+            .args(args)
+            .output()
+            .await
+            .map_err(|e| format!("Failed to run ffmpeg (is it installed?): {}", e))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let message = format!(
+                "ffmpeg failed: {}",
+                stderr.lines().last().unwrap_or("no output")
+            );
+            return Err(message);
+        }
+        summary.video = Some(video.display().to_string());
+        Ok(summary)
+    })
+    .await
+}
+
+/// Images the app was launched with or asked to open before the frontend
+/// was ready. Later opens arrive as "open-image" events instead.
+#[tauri::command]
+pub fn take_pending_images(state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    Ok(state
+        .pending_images
+        .lock_or_recover()
+        .take()
+        .unwrap_or_default())
+}
+
+#[tauri::command]
+pub fn save_bytes_to_file(
+    path: String,
+    data: Vec<u8>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    recorded(&state, "save_bytes_to_file", || {
+        std::fs::write(&path, &data).map_err(|e| e.to_string())
+    })
+}
+
+/// Keep the display awake until turned off again, independently of the
+/// automatic hold during long runs. Returns whether the display is now
+/// being kept awake. Supported on macOS and Windows.
+#[tauri::command]
+pub fn set_prevent_sleep(enabled: bool, state: State<'_, AppState>) -> Result<bool, String> {
+    recorded(&state, "set_prevent_sleep", || {
+        let mut keep_awake = state.keep_awake.lock_or_recover();
+        if enabled {
+            keep_awake.hold(power::MANUAL)?;
+        } else {
+            keep_awake.release(power::MANUAL);
+        }
+        Ok(keep_awake.is_active())
+    })
+}
+
+/// Throttle the live loop and cap the render size, to keep a laptop from
+/// overheating. The frontend paces the loop from the returned config; the
+/// render size cap applies from the next sidecar start.
+#[tauri::command]
+pub fn set_low_power(enabled: bool, app: AppHandle) -> LowPowerConfig {
+    low_power::set(&app, enabled)
+}
+
+/// The low-power config in effect, for a live loop starting up; changes
+/// arrive as "low-power-changed" events.
+#[tauri::command]
+pub fn get_low_power(state: State<'_, AppState>) -> LowPowerConfig {
+    LowPowerConfig::new(state.low_power.load(Ordering::Relaxed))
+}
+
+/// Errors recorded this session (and previous ones, with
+/// `persist_error_log`), oldest first. `limit` keeps only the newest.
+#[tauri::command]
+pub fn get_recent_errors(
+    limit: Option<usize>,
+    state: State<'_, AppState>,
+) -> Result<Vec<ErrorRecord>, String> {
+    Ok(state.errors.lock_or_recover().recent(limit))
+}
+
+/// How stable the sidecar has been this session: how many times it ran,
+/// how often it crashed, and how long runs lasted.
+#[tauri::command]
+pub fn get_stability_report(state: State<'_, AppState>) -> Result<StabilityReport, String> {
+    Ok(state.runs.lock_or_recover().report())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn port_process(pid: u32) -> PortProcess {
+        PortProcess {
+            pid,
+            name: "python3".into(),
+        }
+    }
+
+    #[test]
+    fn pids_to_kill_skips_our_own_process() {
+        let procs = [port_process(10), port_process(20), port_process(30)];
+        assert_eq!(
+            pids_to_kill(&procs, Some(sysinfo::Pid::from_u32(20))),
+            [10, 30].map(sysinfo::Pid::from_u32)
+        );
+    }
+
+    #[test]
+    fn pids_to_kill_lists_each_process_once() {
+        let procs = [port_process(20), port_process(10), port_process(20)];
+        assert_eq!(
+            pids_to_kill(&procs, None),
+            [10, 20].map(sysinfo::Pid::from_u32)
+        );
+    }
+
+    #[test]
+    fn pids_to_kill_is_empty_when_only_we_hold_the_port() {
+        let procs = [port_process(10)];
+        assert!(pids_to_kill(&procs, Some(sysinfo::Pid::from_u32(10))).is_empty());
+    }
 }
@@ -0,0 +1,333 @@
+/// Persistent user settings.
+///
+/// One typed `Settings` struct, validated on load and on every update, is
+/// saved as `settings.json` in the app config dir. Whether its paths exist is
+/// only checked when they're set and when the sidecar starts. Runtime knobs
+/// that other modules read on hot paths (idle timeout, port release wait)
+/// stay in their `AppState` atomics; `Settings::apply` pushes the persisted
+/// values there.
+use crate::persist;
+use crate::sidecar_client::ClientConfig;
+use crate::state::{AppState, DEFAULT_PORT, DEFAULT_PORT_RELEASE_MS};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use tauri::{AppHandle, Manager};
+
+const SETTINGS_FILE: &str = "settings.json";
+
+/// Main window placement, in physical pixels. `x` and `y` are the outer
+/// position; `width` and `height` are the inner size.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct WindowGeometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub maximized: bool,
+}
+
+/// Pen settings saved under a name, usually a tablet's device name.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PressureProfile {
+    pub smoothing: f32,
+    pub pressure_curve: f32,
+}
+
+impl PressureProfile {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.smoothing) {
+            return Err(format!(
+                "smoothing must be between 0 and 1, got {}",
+                self.smoothing
+            ));
+        }
+        if !(self.pressure_curve > 0.0 && self.pressure_curve.is_finite()) {
+            return Err(format!(
+                "pressure_curve must be positive, got {}",
+                self.pressure_curve
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Version written by `export_pressure_curve`. Bump it when the file's
+/// meaning changes; older versions must keep importing.
+pub const PRESSURE_FILE_VERSION: u32 = 1;
+
+/// A pressure profile as saved for sharing, e.g.
+/// `{ "version": 1, "smoothing": 0.2, "pressure_curve": 1.5 }`.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct PressureCurveFile {
+    pub version: u32,
+    #[serde(flatten)]
+    pub profile: PressureProfile,
+}
+
+impl PressureCurveFile {
+    pub fn new(profile: PressureProfile) -> Self {
+        Self {
+            version: PRESSURE_FILE_VERSION,
+            profile,
+        }
+    }
+
+    /// Read and validate a shared pressure curve.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let file: Self = serde_json::from_str(&text)
+            .map_err(|e| format!("{} is not a pressure curve: {}", path.display(), e))?;
+        if file.version == 0 || file.version > PRESSURE_FILE_VERSION {
+            return Err(format!(
+                "{} is pressure curve version {}; this version of Pictaflux reads up to {}",
+                path.display(),
+                file.version,
+                PRESSURE_FILE_VERSION
+            ));
+        }
+        file.profile.validate()?;
+        Ok(file)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Port the sidecar is started on.
+    pub port: u16,
+    /// The session's prompt, restored on the next launch.
+    pub last_prompt: String,
+    /// Latent feedback the sidecar was last started or updated with.
+    pub feedback: f32,
+    /// img2img strength the sidecar was last started or updated with.
+    pub strength: f32,
+    /// Python interpreter to run the sidecar with; `None` uses the sidecar's
+    /// own `.venv`.
+    pub python_path: Option<PathBuf>,
+    /// Allow `start_sidecar` to run a script by absolute path, outside the
+    /// sidecar directory.
+    pub allow_external_scripts: bool,
+    /// Hugging Face cache directory (`HF_HOME`); `None` keeps the default.
+    pub model_cache: Option<PathBuf>,
+    /// How long to wait for the sidecar's READY line.
+    pub startup_timeout_secs: u64,
+    /// How long to wait for the port to free after orphan cleanup.
+    pub port_release_ms: u64,
+    /// How long `process_canvas` waits for the sidecar before giving up.
+    pub process_timeout_secs: u64,
+    /// Stop the sidecar after this many idle seconds; 0 disables.
+    pub idle_timeout_secs: u64,
+    /// Seconds between sidecar health checks; 0 (the default) disables
+    /// them. Opt-in, since a sidecar script that predates the HTTP API
+    /// would otherwise be restarted for failing checks it can't answer.
+    pub health_poll_secs: u64,
+    /// Consecutive failed health checks before the sidecar is declared
+    /// unhealthy.
+    pub health_failure_threshold: u32,
+    /// Stroke smoothing strength, 0 (off) to 1.
+    pub smoothing: f32,
+    /// Exponent applied to raw pen pressure; 1 is linear.
+    pub pressure_curve: f32,
+    /// Saved pen settings by name. A profile named after a tablet device is
+    /// applied when that device enters proximity.
+    pub pressure_profiles: BTreeMap<String, PressureProfile>,
+    /// Main window geometry from the last session; `None` opens it at the
+    /// default size, centered.
+    pub window: Option<WindowGeometry>,
+    /// Timeouts and retries for the sidecar's control API. Raise them for
+    /// slow machines.
+    pub client: ClientConfig,
+    /// Keep the display awake while a batch or loop capture runs.
+    pub prevent_sleep_during_runs: bool,
+    /// Keep the error history across sessions.
+    pub persist_error_log: bool,
+    /// Restart a running sidecar when a setting it only reads at startup
+    /// changes, instead of just reporting that a restart is needed.
+    pub restart_on_settings_change: bool,
+    /// Turn low-power mode on and off with macOS Low Power Mode.
+    pub follow_system_low_power: bool,
+    /// Longest prompt accepted, in characters.
+    pub max_prompt_chars: usize,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            port: DEFAULT_PORT,
+            last_prompt: String::new(),
+            feedback: 0.1,
+            strength: 0.5,
+            python_path: None,
+            allow_external_scripts: false,
+            model_cache: None,
+            startup_timeout_secs: 120,
+            port_release_ms: DEFAULT_PORT_RELEASE_MS,
+            process_timeout_secs: 30,
+            idle_timeout_secs: 0,
+            health_poll_secs: 0,
+            health_failure_threshold: 3,
+            smoothing: 0.0,
+            pressure_curve: 1.0,
+            pressure_profiles: BTreeMap::new(),
+            window: None,
+            client: ClientConfig::default(),
+            prevent_sleep_during_runs: false,
+            persist_error_log: false,
+            restart_on_settings_change: false,
+            follow_system_low_power: false,
+            max_prompt_chars: 1000,
+        }
+    }
+}
+
+/// The settings a sidecar reads when it starts. Everything else in
+/// `Settings` is used by the app itself and applies immediately.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LaunchSettings {
+    pub port: u16,
+    pub python_path: Option<PathBuf>,
+    pub model_cache: Option<PathBuf>,
+}
+
+impl LaunchSettings {
+    /// Names of the settings that differ between `self` and `other`.
+    pub fn changes(&self, other: &LaunchSettings) -> Vec<&'static str> {
+        let mut changes = Vec::new();
+        if self.port != other.port {
+            changes.push("port");
+        }
+        if self.python_path != other.python_path {
+            changes.push("python_path");
+        }
+        if self.model_cache != other.model_cache {
+            changes.push("model_cache");
+        }
+        changes
+    }
+}
+
+impl Settings {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.port == 0 {
+            return Err("port must be non-zero".into());
+        }
+        for (name, value) in [("feedback", self.feedback), ("strength", self.strength)] {
+            if !(value.is_finite() && (0.0..=1.0).contains(&value)) {
+                return Err(format!("{} must be between 0 and 1, got {}", name, value));
+            }
+        }
+        if self.startup_timeout_secs == 0 {
+            return Err("startup_timeout_secs must be at least 1".into());
+        }
+        if self.process_timeout_secs == 0 {
+            return Err("process_timeout_secs must be at least 1".into());
+        }
+        if self.health_failure_threshold == 0 {
+            return Err("health_failure_threshold must be at least 1".into());
+        }
+        if self.max_prompt_chars == 0 {
+            return Err("max_prompt_chars must be at least 1".into());
+        }
+        self.client.validate()?;
+        self.pressure_profile().validate()?;
+        for (name, profile) in &self.pressure_profiles {
+            profile
+                .validate()
+                .map_err(|e| format!("Pressure profile {:?}: {}", name, e))?;
+        }
+        Ok(())
+    }
+
+    /// Check that the configured paths exist. Kept out of `validate`, which
+    /// also runs on load: an unplugged drive shouldn't reset every setting.
+    /// Paths unchanged from `previous` are skipped, so other settings can
+    /// still be edited while one is missing.
+    pub fn check_paths(&self, previous: &Settings) -> Result<(), String> {
+        if let Some(python) = &self.python_path {
+            if previous.python_path.as_ref() != Some(python) && !python.is_file() {
+                return Err(format!("Python not found at {}", python.display()));
+            }
+        }
+        if let Some(dir) = &self.model_cache {
+            if previous.model_cache.as_ref() != Some(dir) && !dir.is_dir() {
+                return Err(format!("Model cache {} is not a directory", dir.display()));
+            }
+        }
+        Ok(())
+    }
+
+    /// The pen settings currently in effect.
+    pub fn pressure_profile(&self) -> PressureProfile {
+        PressureProfile {
+            smoothing: self.smoothing,
+            pressure_curve: self.pressure_curve,
+        }
+    }
+
+    pub fn set_pressure_profile(&mut self, profile: &PressureProfile) {
+        self.smoothing = profile.smoothing;
+        self.pressure_curve = profile.pressure_curve;
+    }
+
+    /// Record the prompt and sidecar parameters in use. Values `validate`
+    /// would reject are skipped. Returns whether anything changed, so
+    /// unchanged values aren't written out again.
+    pub fn remember_session(
+        &mut self,
+        prompt: Option<&str>,
+        feedback: Option<f32>,
+        strength: Option<f32>,
+        port: Option<u16>,
+    ) -> bool {
+        let before = self.clone();
+        if let Some(prompt) = prompt {
+            self.last_prompt = prompt.to_string();
+        }
+        let unit = |v: &f32| v.is_finite() && (0.0..=1.0).contains(v);
+        self.feedback = feedback.filter(unit).unwrap_or(self.feedback);
+        self.strength = strength.filter(unit).unwrap_or(self.strength);
+        // Port 0 means "any free port" for one start, not a preference.
+        self.port = port.filter(|&p| p != 0).unwrap_or(self.port);
+        *self != before
+    }
+
+    pub fn launch_settings(&self) -> LaunchSettings {
+        LaunchSettings {
+            port: self.port,
+            python_path: self.python_path.clone(),
+            model_cache: self.model_cache.clone(),
+        }
+    }
+
+    /// Push values that live in `AppState` atomics.
+    pub fn apply(&self, state: &AppState) {
+        state
+            .idle_secs
+            .store(self.idle_timeout_secs, Ordering::Relaxed);
+        state
+            .port_release_ms
+            .store(self.port_release_ms, Ordering::Relaxed);
+    }
+}
+
+fn settings_path(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_config_dir()
+        .map(|dir| dir.join(SETTINGS_FILE))
+        .map_err(|e| format!("No config directory: {}", e))
+}
+
+/// Load saved settings, falling back to defaults if there are none or they
+/// don't validate (a bad file is backed up rather than overwritten).
+pub fn load(app: &AppHandle) -> Settings {
+    match settings_path(app) {
+        Ok(path) => persist::load_json_or_default(&path, Settings::validate),
+        Err(_) => Settings::default(),
+    }
+}
+
+pub fn save(app: &AppHandle, settings: &Settings) -> Result<(), String> {
+    persist::save_json(&settings_path(app)?, settings)
+}
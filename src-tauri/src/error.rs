@@ -0,0 +1,86 @@
+/// Errors returned by sidecar and canvas-processing commands.
+///
+/// Serialized as the plain display string so the frontend keeps receiving
+/// the same `string` rejection it always has.
+use crate::sidecar_client::ClientError;
+use crate::state::SidecarStatus;
+use std::path::PathBuf;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SidecarError {
+    #[error("Image is {width}x{height}, but the model requires {requirement}")]
+    BadDimensions {
+        width: u32,
+        height: u32,
+        requirement: String,
+    },
+    #[error("Port {port} is still in use{}", by_holder(.holder))]
+    PortInUse {
+        port: u16,
+        /// The listening process, if it could be identified.
+        holder: Option<String>,
+    },
+    #[error("Sidecar script not found; checked {}", list_paths(.checked))]
+    ScriptNotFound { checked: Vec<PathBuf> },
+    /// The sidecar is stopped, starting, or restarting; wait for `Ready`.
+    #[error("Sidecar is not ready ({})", describe_status(.status))]
+    NotReady { status: SidecarStatus },
+    #[error("Sidecar did not respond within {secs}s")]
+    ProcessTimeout { secs: u64 },
+    /// The request to the sidecar failed: connection refused, an HTTP
+    /// error, or a reply that isn't a frame.
+    #[error(transparent)]
+    Client(#[from] ClientError),
+    #[error("Processing was cancelled")]
+    Cancelled,
+    /// A newer `process_canvas` call was dispatched before this one finished.
+    #[error("Frame {frame_id} was superseded by frame {latest}")]
+    Superseded { frame_id: u64, latest: u64 },
+    #[error("Invalid image: {0}")]
+    InvalidImage(String),
+    #[error("{0}")]
+    Other(String),
+}
+
+fn list_paths(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|p| p.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn describe_status(status: &SidecarStatus) -> String {
+    match status {
+        SidecarStatus::Stopped => "stopped".into(),
+        SidecarStatus::Loading => "still loading".into(),
+        SidecarStatus::Ready => "ready".into(),
+        SidecarStatus::Restarting { attempt } => format!("restarting, attempt {}", attempt),
+        SidecarStatus::Error(message) => format!("error: {}", message),
+    }
+}
+
+fn by_holder(holder: &Option<String>) -> String {
+    holder
+        .as_ref()
+        .map(|h| format!(" by {}", h))
+        .unwrap_or_default()
+}
+
+impl From<String> for SidecarError {
+    fn from(message: String) -> Self {
+        Self::Other(message)
+    }
+}
+
+impl From<&str> for SidecarError {
+    fn from(message: &str) -> Self {
+        Self::Other(message.to_string())
+    }
+}
+
+impl serde::Serialize for SidecarError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
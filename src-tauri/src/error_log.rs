@@ -0,0 +1,75 @@
+/// History of errors the app has reported.
+///
+/// Errors usually surface as a toast that is gone a few seconds later. The
+/// failures of commands that talk to the sidecar, and what the watchdog and
+/// health monitor notice, are also kept here for `get_recent_errors`, so an
+/// intermittent problem can be looked at after the fact. With
+/// `Settings::persist_error_log` the history is saved on exit and reloaded
+/// on the next launch.
+use crate::persist;
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Manager};
+
+/// Errors kept before the oldest are dropped.
+pub const MAX_ERRORS: usize = 200;
+
+const ERRORS_FILE: &str = "errors.json";
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct ErrorRecord {
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    /// What failed, e.g. "process_canvas" or "watchdog".
+    pub source: String,
+    pub message: String,
+}
+
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct ErrorLog {
+    records: VecDeque<ErrorRecord>,
+}
+
+impl ErrorLog {
+    pub fn push(&mut self, source: &str, message: String) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        self.records.push_back(ErrorRecord {
+            timestamp,
+            source: source.to_string(),
+            message,
+        });
+        while self.records.len() > MAX_ERRORS {
+            self.records.pop_front();
+        }
+    }
+
+    /// The newest `limit` records (all of them without one), oldest first.
+    pub fn recent(&self, limit: Option<usize>) -> Vec<ErrorRecord> {
+        let skip = limit.map_or(0, |n| self.records.len().saturating_sub(n));
+        self.records.iter().skip(skip).cloned().collect()
+    }
+}
+
+fn errors_path(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map(|dir| dir.join(ERRORS_FILE))
+        .map_err(|e| format!("No data directory: {}", e))
+}
+
+/// The history saved by the previous session, or an empty one.
+pub fn load(app: &AppHandle) -> ErrorLog {
+    match errors_path(app) {
+        Ok(path) => persist::load_json_or_default(&path, |_| Ok(())),
+        Err(_) => ErrorLog::default(),
+    }
+}
+
+pub fn save(app: &AppHandle, log: &ErrorLog) -> Result<(), String> {
+    persist::save_json(&errors_path(app)?, log)
+}
@@ -0,0 +1,39 @@
+/// Window-targeted event emission.
+///
+/// `AppHandle::emit` broadcasts to every window, which goes wrong as soon as
+/// there is more than the canvas window: a tool palette would receive (and
+/// try to handle) tablet strokes too. Everything the backend emits goes
+/// through here and is delivered to one window by label instead.
+use crate::state::{AppState, LockExt};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Label of the main canvas window in `tauri.conf.json`.
+pub const MAIN_WINDOW: &str = "main";
+
+/// Emit a sidecar/processing event to the main canvas window.
+pub fn emit_sidecar<S: Serialize + Clone>(app: &AppHandle, event: &str, payload: S) {
+    let _ = app.emit_to(MAIN_WINDOW, event, payload);
+}
+
+/// Emit a tablet event to the window set by `set_tablet_target_window`,
+/// recording it if a session recording is active.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+pub fn emit_tablet<S: Serialize + Clone>(app: &AppHandle, event: &str, payload: S) {
+    record(app, event, &payload);
+    deliver_tablet(app, event, payload);
+}
+
+/// Append an entry to the active session recording, if any.
+pub fn record<S: Serialize>(app: &AppHandle, event: &str, payload: &S) {
+    if let Some(recorder) = app.state::<AppState>().recorder.lock_or_recover().as_mut() {
+        recorder.record(event, payload);
+    }
+}
+
+/// Deliver a tablet event without recording it, for replays.
+pub fn deliver_tablet<S: Serialize + Clone>(app: &AppHandle, event: &str, payload: S) {
+    let state = app.state::<AppState>();
+    let label = state.tablet_target.lock_or_recover().clone();
+    let _ = app.emit_to(label.as_str(), event, payload);
+}
@@ -0,0 +1,60 @@
+/// Images handed to the app by the OS.
+///
+/// Files arrive three ways: as command-line arguments on first launch
+/// (Windows and Linux), as arguments forwarded from a second launch by the
+/// single-instance plugin, and as macOS open-file events from dropping a
+/// file on the dock icon or double-clicking it. Only images in a supported
+/// format are passed on. Until the frontend collects the launch images with
+/// `take_pending_images` they are queued, since it isn't listening yet;
+/// afterwards each becomes an "open-image" event.
+use crate::canvas;
+use crate::events;
+use crate::state::{AppState, LockExt};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+/// Serialized payload for the "open-image" event.
+#[derive(Clone, serde::Serialize)]
+struct OpenImagePayload {
+    path: String,
+}
+
+/// File paths in a command line, resolved against `cwd`. Skips the program
+/// name and anything that looks like a flag.
+pub fn paths_from_args(args: &[String], cwd: &Path) -> Vec<PathBuf> {
+    args.iter()
+        .skip(1)
+        .filter(|arg| !arg.starts_with('-'))
+        .map(|arg| cwd.join(arg))
+        .collect()
+}
+
+/// Hand each supported image to the frontend and bring the main window
+/// forward. Anything else is logged and skipped.
+pub fn open_paths(app: &AppHandle, paths: Vec<PathBuf>) {
+    let mut opened = false;
+    for path in paths {
+        if !canvas::is_supported_image(&path) {
+            eprintln!("[open] ignoring {}: not a supported image", path.display());
+            continue;
+        }
+        let path = path.display().to_string();
+        opened = true;
+        if let Some(pending) = app
+            .state::<AppState>()
+            .pending_images
+            .lock_or_recover()
+            .as_mut()
+        {
+            pending.push(path);
+            continue;
+        }
+        events::emit_sidecar(app, "open-image", OpenImagePayload { path });
+    }
+    if opened {
+        if let Some(window) = app.get_webview_window(events::MAIN_WINDOW) {
+            let _ = window.unminimize();
+            let _ = window.set_focus();
+        }
+    }
+}
@@ -0,0 +1,42 @@
+/// Memory of the first GPU in MB, for the pre-start VRAM check.
+///
+/// NVIDIA GPUs are asked through `nvidia-smi`. Apple Silicon shares system
+/// memory with the GPU, so there total and available RAM stand in for VRAM.
+/// Anything else reports nothing rather than a guess.
+pub struct GpuMemory {
+    pub total_mb: u64,
+    pub free_mb: u64,
+}
+
+#[cfg(target_os = "macos")]
+pub async fn query() -> Option<GpuMemory> {
+    let mut system = sysinfo::System::new();
+    system.refresh_memory();
+    let mb = |bytes: u64| bytes / (1024 * 1024);
+    Some(GpuMemory {
+        total_mb: mb(system.total_memory()),
+        free_mb: mb(system.available_memory()),
+    })
+}
+
+#[cfg(not(target_os = "macos"))]
+pub async fn query() -> Option<GpuMemory> {
+    let output = tokio::process::Command::new("nvidia-smi")
+        .args([
+            "--query-gpu=memory.total,memory.free",
+            "--format=csv,noheader,nounits",
+        ])
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    // One "total, free" line per GPU; the sidecar uses the first.
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let (total, free) = stdout.lines().next()?.split_once(',')?;
+    Some(GpuMemory {
+        total_mb: total.trim().parse().ok()?,
+        free_mb: free.trim().parse().ok()?,
+    })
+}
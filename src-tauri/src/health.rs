@@ -0,0 +1,124 @@
+/// Health polling for the sidecar.
+///
+/// The crash watchdog only notices a sidecar that exits. One that deadlocks
+/// keeps its process alive and just stops answering, so this polls its
+/// health endpoint and demotes it to `Error` after repeated failures,
+/// restarting it when auto-restart is enabled.
+use crate::commands::{relaunch_args, set_status, shutdown_sidecar, start_with_args};
+use crate::events;
+use crate::sidecar_client::{self, SidecarClient};
+use crate::stability::RunEnd;
+use crate::state::{AppState, LockExt, SidecarStatus};
+use std::hash::{BuildHasher, Hasher};
+use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+/// How long the loop sleeps while polling is disabled.
+const DISABLED_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Serialized payload for the "sidecar-unhealthy" event.
+#[derive(Clone, serde::Serialize)]
+struct UnhealthyPayload {
+    failures: u32,
+    restarting: bool,
+}
+
+pub fn spawn_health_monitor(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut failures = 0;
+        loop {
+            let state = app.state::<AppState>();
+            let (interval_secs, threshold, client) = {
+                let s = state.settings.lock_or_recover();
+                (
+                    s.health_poll_secs,
+                    s.health_failure_threshold,
+                    s.client.clone(),
+                )
+            };
+            if interval_secs == 0 {
+                failures = 0;
+                tokio::time::sleep(DISABLED_INTERVAL).await;
+                continue;
+            }
+            tokio::time::sleep(jittered(Duration::from_secs(interval_secs))).await;
+
+            let Some(addr) = ready_addr(&state) else {
+                failures = 0;
+                continue;
+            };
+            let healthy = SidecarClient::new(addr, client)
+                .probe(Duration::from_secs(interval_secs.min(5)))
+                .await;
+            if healthy {
+                failures = 0;
+                continue;
+            }
+            failures += 1;
+            if failures < threshold {
+                continue;
+            }
+
+            let restarting = state.auto_restart_attempts.load(Ordering::Relaxed) > 0;
+            eprintln!(
+                "[sidecar] unhealthy after {} failed health checks",
+                failures
+            );
+            state.end_run(RunEnd::Crashed);
+            state.record_error(
+                "health",
+                format!("Unhealthy after {} failed health checks", failures),
+            );
+            let _ = set_status(
+                &app,
+                &state,
+                SidecarStatus::Error("Sidecar stopped responding".into()),
+            );
+            events::emit_sidecar(
+                &app,
+                "sidecar-unhealthy",
+                UnhealthyPayload {
+                    failures,
+                    restarting,
+                },
+            );
+            failures = 0;
+            if restarting {
+                restart(&app, &state).await;
+            }
+        }
+    });
+}
+
+/// Address of a sidecar that claims to be ready and still has a process.
+fn ready_addr(state: &AppState) -> Option<SocketAddr> {
+    let sidecar = state.sidecar.lock_or_recover();
+    (sidecar.child.is_some() && matches!(sidecar.status, SidecarStatus::Ready))
+        .then(|| sidecar_client::connect_addr(sidecar.host, sidecar.port))
+}
+
+async fn restart(app: &AppHandle, state: &AppState) {
+    let Some(args) = relaunch_args(state) else {
+        return;
+    };
+    let _ = shutdown_sidecar(app, state).await;
+    match start_with_args(app, state, args).await {
+        Ok(_) => events::emit_sidecar(app, "sidecar-resynced", ()),
+        Err(e) => {
+            eprintln!("[sidecar] restart after failed health checks failed: {}", e);
+            state.record_error("health", format!("Restart failed: {}", e));
+        }
+    }
+}
+
+/// `base` scaled by a random factor in 0.8..1.2, so several app instances
+/// don't poll in lockstep.
+fn jittered(base: Duration) -> Duration {
+    let random = std::collections::hash_map::RandomState::new()
+        .build_hasher()
+        .finish();
+    let factor = 0.8 + 0.4 * (random as f64 / u64::MAX as f64);
+    base.mul_f64(factor)
+}
@@ -0,0 +1,55 @@
+/// Idle auto-stop for the sidecar.
+///
+/// The diffusion server holds its model in GPU memory for as long as it
+/// runs. When an idle timeout is configured, this stops it once no
+/// processing call has arrived for that long.
+use crate::commands::shutdown_sidecar;
+use crate::events;
+use crate::state::{AppState, LockExt, SidecarStatus};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+/// How often the idle timer is checked.
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Serialized payload for the "sidecar-idle-stopped" event.
+#[derive(Clone, serde::Serialize)]
+struct IdleStoppedPayload {
+    idle_secs: u64,
+}
+
+pub fn spawn_idle_monitor(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            let state = app.state::<AppState>();
+            let Some(idle_secs) = idle_timeout_elapsed(&state) else {
+                continue;
+            };
+            if let Ok(true) = shutdown_sidecar(&app, &state).await {
+                events::emit_sidecar(
+                    &app,
+                    "sidecar-idle-stopped",
+                    IdleStoppedPayload { idle_secs },
+                );
+            }
+        }
+    });
+}
+
+/// Returns the configured timeout if it is enabled, the sidecar is ready,
+/// and nothing has touched it for at least that long.
+fn idle_timeout_elapsed(state: &AppState) -> Option<u64> {
+    let idle_secs = state.idle_secs.load(Ordering::Relaxed);
+    if idle_secs == 0 {
+        return None;
+    }
+    let ready = {
+        let s = state.sidecar.lock_or_recover();
+        s.child.is_some() && matches!(s.status, SidecarStatus::Ready)
+    };
+    let idle = state.last_activity.lock_or_recover().elapsed() >= Duration::from_secs(idle_secs);
+    (ready && idle).then_some(idle_secs)
+}
@@ -1,43 +1,221 @@
+use state::LockExt;
 use tauri::Manager;
 
+mod canvas;
+mod cmdline;
 mod commands;
+mod config;
+mod error;
+mod error_log;
+mod events;
+mod file_open;
+mod gpu;
+mod health;
+mod idle;
+mod log_buffer;
+mod log_events;
+mod loop_capture;
+mod low_power;
+mod models;
+mod permissions;
+mod persist;
+mod power;
+mod progress;
+mod prompt;
+mod recording;
+mod screenshot;
+mod sidecar_client;
+mod sidecar_protocol;
+mod stability;
 mod state;
+mod watchdog;
+mod window_state;
 
 #[cfg(target_os = "macos")]
 mod tablet;
 
+#[cfg(feature = "test-support")]
+mod mock_sidecar;
+
+/// What integration tests need to drive the sidecar client against a mock.
+#[cfg(feature = "test-support")]
+pub mod test_support {
+    pub use crate::mock_sidecar::{MockBody, MockResponse, MockSidecar, RecordedRequest};
+    pub use crate::models::ModelInfo;
+    pub use crate::sidecar_client::{ClientConfig, ClientError, FlushCacheResponse, SidecarClient};
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
+        // Must be registered first so a second launch exits before setup.
+        .plugin(tauri_plugin_single_instance::init(|app, args, cwd| {
+            file_open::open_paths(app, file_open::paths_from_args(&args, cwd.as_ref()));
+        }))
         .manage(state::AppState::new())
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_dialog::init())
         .invoke_handler(tauri::generate_handler![
             commands::process_canvas,
+            commands::process_test_pattern,
+            commands::clear_canvas,
+            commands::get_frame_generation,
+            commands::get_current_prompt,
+            commands::process_batch,
+            commands::cancel_batch,
+            commands::set_dimension_constraints,
+            commands::set_frame_compression,
+            commands::get_settings,
+            commands::update_settings,
+            commands::reset_settings,
+            commands::save_pressure_profile,
+            commands::load_pressure_profile,
+            commands::list_pressure_profiles,
+            commands::export_pressure_curve,
+            commands::import_pressure_curve,
             commands::start_sidecar,
             commands::stop_sidecar,
+            commands::restart_sidecar,
+            commands::force_kill_all_sidecars,
+            commands::cancel_startup,
+            commands::set_feedback_mode,
+            commands::set_mode,
+            commands::validate_prompt,
+            commands::set_seed_lock,
+            commands::set_strength_schedule,
+            commands::get_strength_schedule,
+            commands::update_sidecar_params,
+            commands::set_idle_timeout,
+            commands::set_auto_restart,
+            commands::set_port_release_timeout,
+            commands::who_has_port,
             commands::get_sidecar_status,
+            commands::get_port,
+            commands::is_sidecar_running,
+            commands::open_sidecar_url,
+            commands::reset_sidecar_state,
+            commands::flush_gpu_cache,
+            commands::get_available_models,
+            commands::check_gpu_memory,
+            commands::set_models_dir,
+            commands::switch_model,
+            commands::set_model_cache,
+            commands::get_last_sidecar_command,
+            commands::get_sidecar_logs,
+            commands::get_sidecar_metrics,
+            commands::set_log_limits,
+            commands::probe_tablet_capabilities,
+            commands::check_input_monitoring_permission,
+            commands::request_input_monitoring_permission,
+            commands::set_tablet_target_window,
+            commands::set_tablet_batch_mode,
+            commands::set_tablet_focus_gating,
+            commands::ping_tablet,
+            commands::get_tablet_devices,
+            commands::set_active_tablet_device,
+            commands::start_recording,
+            commands::stop_recording,
+            commands::replay_recording,
+            commands::replay_step,
+            commands::get_build_info,
+            commands::take_screenshot,
+            commands::start_loop_capture,
+            commands::capture_loop_frame,
+            commands::stop_loop_capture,
+            commands::take_pending_images,
             commands::save_bytes_to_file,
+            commands::set_prevent_sleep,
+            commands::set_low_power,
+            commands::get_low_power,
+            commands::get_recent_errors,
+            commands::get_stability_report,
+            commands::get_setup_instructions,
+            commands::run_sidecar_setup,
         ])
         .setup(|app| {
+            let settings = config::load(app.handle());
+            let app_state = app.state::<state::AppState>();
+            settings.apply(&app_state);
+            *app_state.last_prompt.lock_or_recover() = settings.last_prompt.clone();
+            if settings.persist_error_log {
+                *app_state.errors.lock_or_recover() = error_log::load(app.handle());
+            }
+            *app_state.settings.lock_or_recover() = settings;
+            idle::spawn_idle_monitor(app.handle().clone());
+            health::spawn_health_monitor(app.handle().clone());
+            watchdog::spawn_crash_watchdog(app.handle().clone());
+            log_events::spawn_log_flusher(app.handle().clone());
+            low_power::spawn_system_monitor(app.handle().clone());
+            window_state::restore(app.handle());
+            if let Ok(cwd) = std::env::current_dir() {
+                let args: Vec<String> = std::env::args().collect();
+                file_open::open_paths(app.handle(), file_open::paths_from_args(&args, &cwd));
+            }
             #[cfg(target_os = "macos")]
-            tablet::start_tablet_monitor(app.handle().clone());
+            if let Err(e) =
+                tablet::start_tablet_monitor(app.handle().clone(), tablet::DEFAULT_MAX_RATE_HZ)
+            {
+                tablet::report_init_failure(app.handle(), &e);
+            }
             Ok(())
         })
-        .on_window_event(|window, event| {
-            if let tauri::WindowEvent::CloseRequested { .. } = event {
+        .on_window_event(|window, event| match event {
+            tauri::WindowEvent::Moved(_) | tauri::WindowEvent::Resized(_) => {
+                window_state::schedule_save(window);
+            }
+            tauri::WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                commands::scale_factor_changed(window, *scale_factor);
+            }
+            tauri::WindowEvent::Focused(focused) => {
                 let app_state = window.state::<state::AppState>();
-                let mut child_to_kill = None;
-                if let Ok(mut sidecar) = app_state.sidecar.lock() {
-                    child_to_kill = sidecar.child.take();
-                    sidecar.status = state::SidecarStatus::Stopped;
-                };
-                if let Some(mut child) = child_to_kill {
-                    let _ = child.start_kill();
-                    let _ = child.try_wait();
+                let mut current = app_state.focused_window.lock_or_recover();
+                if *focused {
+                    *current = Some(window.label().to_string());
+                } else if current.as_deref() == Some(window.label()) {
+                    *current = None;
                 }
             }
+            // Closing the main window quits, so the sidecar is stopped
+            // first; the window closes once it has exited.
+            tauri::WindowEvent::CloseRequested { api, .. }
+                if window.label() == events::MAIN_WINDOW =>
+            {
+                let closing = window.clone();
+                if commands::shutdown_for_exit(window.app_handle(), move || {
+                    let _ = closing.destroy();
+                }) {
+                    api.prevent_close();
+                }
+            }
+            _ => {}
         })
-        .run(tauri::generate_context!())
-        .expect("error while running Pictaflux");
+        .build(tauri::generate_context!())
+        .expect("error while running Pictaflux")
+        .run(|app, event| match event {
+            // Quitting (e.g. Cmd+Q) doesn't close windows first.
+            tauri::RunEvent::ExitRequested { api, code, .. } => {
+                let exiting = app.clone();
+                if commands::shutdown_for_exit(app, move || exiting.exit(code.unwrap_or(0))) {
+                    api.prevent_exit();
+                }
+            }
+            tauri::RunEvent::Exit => {
+                let app_state = app.state::<state::AppState>();
+                app_state.keep_awake.lock_or_recover().release_all();
+                let persist = app_state.settings.lock_or_recover().persist_error_log;
+                if persist {
+                    let errors = app_state.errors.lock_or_recover();
+                    if let Err(e) = error_log::save(app, &errors) {
+                        eprintln!("[errors] failed to save: {}", e);
+                    }
+                }
+            }
+            // Files dropped on the dock icon or opened from Finder.
+            #[cfg(target_os = "macos")]
+            tauri::RunEvent::Opened { urls } => {
+                let paths = urls.into_iter().filter_map(|url| url.to_file_path().ok());
+                file_open::open_paths(app, paths.collect());
+            }
+            _ => {}
+        });
 }
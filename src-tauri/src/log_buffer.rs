@@ -0,0 +1,110 @@
+/// Ring buffer of recent sidecar output.
+///
+/// Bounded by total bytes as well as line count: a single pathological
+/// line (a giant traceback, a dumped tensor) can be huge, so a line cap
+/// alone doesn't keep memory predictable.
+use std::collections::VecDeque;
+
+pub const DEFAULT_MAX_LINES: usize = 2000;
+pub const DEFAULT_MAX_BYTES: usize = 1024 * 1024;
+pub const DEFAULT_MAX_LINE_BYTES: usize = 4096;
+
+/// Appended to lines cut at `max_line_bytes`.
+const TRUNCATION_MARKER: &str = "… [truncated]";
+
+/// Lines queued for the next "sidecar-log-batch" event before further ones
+/// are only counted. The ring buffer keeps them either way.
+const MAX_PENDING_LINES: usize = 500;
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct LogLine {
+    /// "stdout", "stderr", or "app" for lines we log ourselves.
+    pub stream: &'static str,
+    pub text: String,
+}
+
+/// Serialized payload for the "sidecar-log-batch" event.
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct LogBatch {
+    /// Tagged with their stream, so stderr tracebacks can be told apart
+    /// from ordinary progress output.
+    pub lines: Vec<LogLine>,
+    /// Lines left out of this batch because too many arrived at once.
+    pub dropped: u64,
+}
+
+pub struct LogBuffer {
+    lines: VecDeque<LogLine>,
+    bytes: usize,
+    /// Lines not yet sent to the frontend.
+    pending: Vec<LogLine>,
+    dropped: u64,
+    pub max_lines: usize,
+    pub max_bytes: usize,
+    pub max_line_bytes: usize,
+}
+
+impl LogBuffer {
+    pub fn new() -> Self {
+        Self {
+            lines: VecDeque::new(),
+            bytes: 0,
+            pending: Vec::new(),
+            dropped: 0,
+            max_lines: DEFAULT_MAX_LINES,
+            max_bytes: DEFAULT_MAX_BYTES,
+            max_line_bytes: DEFAULT_MAX_LINE_BYTES,
+        }
+    }
+
+    pub fn push(&mut self, stream: &'static str, text: &str) {
+        let text = truncate_line(text, self.max_line_bytes);
+        if self.pending.len() < MAX_PENDING_LINES {
+            self.pending.push(LogLine {
+                stream,
+                text: text.clone(),
+            });
+        } else {
+            self.dropped += 1;
+        }
+        self.bytes += text.len();
+        self.lines.push_back(LogLine { stream, text });
+        self.evict();
+    }
+
+    pub fn lines(&self) -> Vec<LogLine> {
+        self.lines.iter().cloned().collect()
+    }
+
+    /// Lines pushed since the last call, or `None` if there were none.
+    pub fn take_pending(&mut self) -> Option<LogBatch> {
+        if self.pending.is_empty() && self.dropped == 0 {
+            return None;
+        }
+        Some(LogBatch {
+            lines: std::mem::take(&mut self.pending),
+            dropped: std::mem::take(&mut self.dropped),
+        })
+    }
+
+    /// Drop the oldest lines until both limits hold again.
+    pub fn evict(&mut self) {
+        while self.lines.len() > self.max_lines || self.bytes > self.max_bytes {
+            match self.lines.pop_front() {
+                Some(line) => self.bytes -= line.text.len(),
+                None => break,
+            }
+        }
+    }
+}
+
+fn truncate_line(text: &str, max_bytes: usize) -> String {
+    if text.len() <= max_bytes {
+        return text.to_string();
+    }
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}{}", &text[..end], TRUNCATION_MARKER)
+}
@@ -0,0 +1,29 @@
+/// Batched delivery of sidecar output to the frontend.
+///
+/// A verbose sidecar can print thousands of lines a second, and one IPC
+/// event per line is enough to stall the webview. Lines are queued in the
+/// log buffer as they arrive and flushed here as a single
+/// "sidecar-log-batch" event at most every `FLUSH_INTERVAL`. The readers
+/// feeding the buffer run for the life of the child's pipes, so they end on
+/// their own when the sidecar is stopped.
+use crate::events;
+use crate::state::{AppState, LockExt};
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+const FLUSH_INTERVAL: Duration = Duration::from_millis(100);
+
+pub fn spawn_log_flusher(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            let state = app.state::<AppState>();
+            let batch = state.logs.lock_or_recover().take_pending();
+            if let Some(batch) = batch {
+                events::emit_sidecar(&app, "sidecar-log-batch", batch);
+            }
+        }
+    });
+}
@@ -0,0 +1,60 @@
+/// Capturing the live feedback loop to disk.
+///
+/// The loop runs in the frontend, which hands each result frame to
+/// `capture_loop_frame` while a capture is active. Frames are stored as
+/// numbered PNGs, whatever format the sidecar sent them in, so ffmpeg can
+/// assemble them into a video afterwards.
+use crate::canvas;
+use std::path::{Path, PathBuf};
+
+pub struct LoopCapture {
+    pub dir: PathBuf,
+    /// Frames claimed so far, including any still being written.
+    pub frames: u64,
+}
+
+impl LoopCapture {
+    pub fn create(dir: &Path) -> Result<Self, String> {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            frames: 0,
+        })
+    }
+
+    /// Reserve the next frame number and return the path to write it to.
+    pub fn claim_frame(&mut self) -> PathBuf {
+        let path = frame_path(&self.dir, self.frames);
+        self.frames += 1;
+        path
+    }
+}
+
+fn frame_path(dir: &Path, index: u64) -> PathBuf {
+    dir.join(format!("frame_{:06}.png", index))
+}
+
+/// Convert an encoded frame to PNG and write it to `path`.
+pub fn write_frame(path: &Path, data: &[u8]) -> Result<(), String> {
+    let (image, _) = canvas::decode_bytes(data).map_err(|e| e.to_string())?;
+    let png = canvas::encode_png(&image).map_err(|e| e.to_string())?;
+    std::fs::write(path, png).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// ffmpeg arguments that encode the frames in `dir` as an H.264 mp4.
+pub fn ffmpeg_args(dir: &Path, fps: u32, output: &Path) -> Vec<String> {
+    vec![
+        "-y".to_string(),
+        "-framerate".to_string(),
+        fps.to_string(),
+        "-i".to_string(),
+        dir.join("frame_%06d.png").display().to_string(),
+        "-c:v".to_string(),
+        "libx264".to_string(),
+        // Most players can't decode the 4:4:4 output libx264 picks for PNG.
+        "-pix_fmt".to_string(),
+        "yuv420p".to_string(),
+        output.display().to_string(),
+    ]
+}
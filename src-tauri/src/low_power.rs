@@ -0,0 +1,104 @@
+/// Low-power mode for battery and thermally constrained machines.
+///
+/// The live loop sends the next frame as soon as the last one returns, which
+/// keeps the GPU pegged for as long as it runs. In low-power mode the
+/// frontend spaces frames at least `LOW_POWER_MIN_FRAME_MS` apart and new
+/// sidecar starts render at no more than `LOW_POWER_RENDER_SIZE`. With
+/// `Settings::follow_system_low_power`, the mode tracks macOS Low Power Mode.
+use crate::events;
+use crate::state::{AppState, LockExt};
+use std::sync::atomic::Ordering;
+use tauri::{AppHandle, Manager};
+
+/// Shortest live-loop frame interval in low-power mode, i.e. 4 FPS.
+pub const LOW_POWER_MIN_FRAME_MS: u64 = 250;
+
+/// Largest render size a sidecar starts with in low-power mode.
+pub const LOW_POWER_RENDER_SIZE: u16 = 256;
+
+/// Serialized payload for the "low-power-changed" event; also what
+/// `set_low_power` returns.
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct LowPowerConfig {
+    pub enabled: bool,
+    /// Minimum gap between live-loop frames; 0 leaves the loop unthrottled.
+    pub min_frame_interval_ms: u64,
+    /// Render size cap for sidecar starts, if any. A running sidecar keeps
+    /// its size until restarted.
+    pub max_render_size: Option<u16>,
+}
+
+impl LowPowerConfig {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            min_frame_interval_ms: if enabled { LOW_POWER_MIN_FRAME_MS } else { 0 },
+            max_render_size: enabled.then_some(LOW_POWER_RENDER_SIZE),
+        }
+    }
+}
+
+/// Switch low-power mode and tell the frontend, which paces the loop.
+pub fn set(app: &AppHandle, enabled: bool) -> LowPowerConfig {
+    let state = app.state::<AppState>();
+    let was = state.low_power.swap(enabled, Ordering::Relaxed);
+    let config = LowPowerConfig::new(enabled);
+    if was != enabled {
+        eprintln!(
+            "[power] Low-power mode {}",
+            if enabled { "on" } else { "off" }
+        );
+        events::emit_sidecar(app, "low-power-changed", config.clone());
+    }
+    config
+}
+
+/// The render size a sidecar should start with, given the requested one.
+pub fn render_size(state: &AppState, requested: u16) -> u16 {
+    if state.low_power.load(Ordering::Relaxed) {
+        requested.min(LOW_POWER_RENDER_SIZE)
+    } else {
+        requested
+    }
+}
+
+/// Whether macOS Low Power Mode is on.
+#[cfg(target_os = "macos")]
+fn system_low_power() -> bool {
+    objc2_foundation::NSProcessInfo::processInfo().isLowPowerModeEnabled()
+}
+
+/// How often the system power state is checked.
+#[cfg(target_os = "macos")]
+const CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Follow macOS Low Power Mode while `follow_system_low_power` is set. Only
+/// changes in the system state are applied, so a manual `set_low_power`
+/// holds until the system switches again.
+#[cfg(target_os = "macos")]
+pub fn spawn_system_monitor(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        let mut last = None;
+        loop {
+            interval.tick().await;
+            let follow = app
+                .state::<AppState>()
+                .settings
+                .lock_or_recover()
+                .follow_system_low_power;
+            if !follow {
+                last = None;
+                continue;
+            }
+            let system = system_low_power();
+            if last != Some(system) {
+                last = Some(system);
+                set(&app, system);
+            }
+        }
+    });
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn spawn_system_monitor(_app: AppHandle) {}
@@ -0,0 +1,254 @@
+/// In-process stand-in for the sidecar's HTTP API, for tests.
+///
+/// Answers the control endpoints `SidecarClient` calls with canned
+/// responses, so the client and the commands built on it can be exercised
+/// without spawning Python. Each endpoint's response can be replaced (an
+/// error status, a different API version, a slow reply), and every request
+/// is recorded for assertions. Only built with the `test-support` feature.
+///
+/// The server speaks just enough HTTP/1.1 for reqwest: one request per
+/// connection, `Content-Length` bodies, and `Connection: close` replies.
+use crate::sidecar_client::{API_HEADER, API_VERSION};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+/// What an endpoint replies with.
+#[derive(Clone, Debug)]
+pub struct MockResponse {
+    pub status: u16,
+    pub body: MockBody,
+    /// Sent as `X-Pictaflux-Api`; `None` leaves the header out.
+    pub api_version: Option<String>,
+    /// Wait this long before replying, to trigger client timeouts.
+    pub delay: Duration,
+}
+
+#[derive(Clone, Debug)]
+pub enum MockBody {
+    Fixed(Vec<u8>),
+    /// Send the request body back, like the frame round trip.
+    Echo,
+}
+
+impl MockResponse {
+    pub fn json(status: u16, body: serde_json::Value) -> Self {
+        Self {
+            status,
+            body: MockBody::Fixed(body.to_string().into_bytes()),
+            api_version: Some(API_VERSION.to_string()),
+            delay: Duration::ZERO,
+        }
+    }
+
+    pub fn status(status: u16) -> Self {
+        Self::json(status, serde_json::json!({}))
+    }
+
+    pub fn echo() -> Self {
+        Self {
+            body: MockBody::Echo,
+            ..Self::status(200)
+        }
+    }
+
+    pub fn with_api_version(mut self, version: Option<&str>) -> Self {
+        self.api_version = version.map(str::to_string);
+        self
+    }
+
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+}
+
+/// A request the mock received.
+#[derive(Clone, Debug)]
+pub struct RecordedRequest {
+    pub method: String,
+    /// Without the leading slash, e.g. "health".
+    pub endpoint: String,
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+#[derive(Default)]
+struct MockState {
+    /// Keyed by (method, endpoint).
+    responses: HashMap<(String, String), MockResponse>,
+    requests: Vec<RecordedRequest>,
+}
+
+pub struct MockSidecar {
+    addr: SocketAddr,
+    state: Arc<Mutex<MockState>>,
+    server: JoinHandle<()>,
+}
+
+impl MockSidecar {
+    /// Listen on an ephemeral loopback port with the default responses: a
+    /// healthy sidecar with one model loaded that accepts every control
+    /// call and echoes frames sent to `process`.
+    pub async fn start() -> std::io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(MockState::default()));
+        let server = tokio::spawn(serve(listener, state.clone()));
+        let mock = Self {
+            addr,
+            state,
+            server,
+        };
+        mock.respond(
+            "GET",
+            "health",
+            MockResponse::json(200, serde_json::json!({ "status": "ok" })),
+        );
+        mock.respond(
+            "GET",
+            "models",
+            MockResponse::json(
+                200,
+                serde_json::json!([{
+                    "id": "sdxs",
+                    "name": "SDXS",
+                    "path": "models/sdxs.safetensors",
+                    "size_mb": 1024.0,
+                }]),
+            ),
+        );
+        mock.respond("POST", "model", MockResponse::status(200));
+        mock.respond("POST", "model_cache", MockResponse::status(200));
+        mock.respond("POST", "params", MockResponse::status(200));
+        mock.respond(
+            "POST",
+            "flush_cache",
+            MockResponse::json(200, serde_json::json!({ "freed_mb": 512.0 })),
+        );
+        mock.respond("POST", "process", MockResponse::echo());
+        Ok(mock)
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn base_url(&self) -> String {
+        crate::sidecar_client::base_url(self.addr)
+    }
+
+    /// Reply to `method endpoint` with `response` from now on. Endpoints
+    /// without a response get a 404.
+    pub fn respond(&self, method: &str, endpoint: &str, response: MockResponse) {
+        if let Ok(mut state) = self.state.lock() {
+            state
+                .responses
+                .insert((method.to_string(), endpoint.to_string()), response);
+        }
+    }
+
+    /// Every request received so far, oldest first.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.state
+            .lock()
+            .map(|state| state.requests.clone())
+            .unwrap_or_default()
+    }
+
+    /// How many times `method endpoint` was called, e.g. to count retries.
+    pub fn hits(&self, method: &str, endpoint: &str) -> usize {
+        self.requests()
+            .iter()
+            .filter(|r| r.method == method && r.endpoint == endpoint)
+            .count()
+    }
+}
+
+impl Drop for MockSidecar {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+async fn serve(listener: TcpListener, state: Arc<Mutex<MockState>>) {
+    while let Ok((stream, _)) = listener.accept().await {
+        let state = state.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle(stream, &state).await {
+                eprintln!("[mock-sidecar] {}", e);
+            }
+        });
+    }
+}
+
+async fn handle(stream: TcpStream, state: &Mutex<MockState>) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let endpoint = parts
+        .next()
+        .unwrap_or_default()
+        .trim_start_matches('/')
+        .to_string();
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+    let length = headers
+        .get("content-length")
+        .and_then(|l| l.parse().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).await?;
+
+    let response = {
+        let mut state = state
+            .lock()
+            .map_err(|e| std::io::Error::other(e.to_string()))?;
+        let response = state
+            .responses
+            .get(&(method.clone(), endpoint.clone()))
+            .cloned()
+            .unwrap_or_else(|| MockResponse::status(404));
+        state.requests.push(RecordedRequest {
+            method,
+            endpoint,
+            headers,
+            body: body.clone(),
+        });
+        response
+    };
+
+    tokio::time::sleep(response.delay).await;
+    let payload = match response.body {
+        MockBody::Fixed(bytes) => bytes,
+        MockBody::Echo => body,
+    };
+    let mut head = format!(
+        "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response.status,
+        payload.len()
+    );
+    if let Some(version) = &response.api_version {
+        head.push_str(&format!("{}: {}\r\n", API_HEADER, version));
+    }
+    head.push_str("\r\n");
+    let mut stream = reader.into_inner();
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(&payload).await?;
+    stream.shutdown().await
+}
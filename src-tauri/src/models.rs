@@ -0,0 +1,82 @@
+/// Model discovery for the model picker.
+///
+/// A running sidecar is the authority on what it can load. When it isn't
+/// running we fall back to scanning the models directory for weight files,
+/// which is close enough to populate the picker before the first start.
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// How long a model list is reused before asking again.
+pub const CACHE_TTL: Duration = Duration::from_secs(10);
+
+/// Weight file extensions recognized when scanning. CoreML packages are
+/// directories, the rest are single files.
+const WEIGHT_EXTENSIONS: [&str; 6] = ["safetensors", "ckpt", "pt", "bin", "mlpackage", "mlmodelc"];
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct ModelInfo {
+    pub id: String,
+    pub name: String,
+    pub path: String,
+    pub size_mb: f64,
+}
+
+pub struct ModelCache {
+    pub fetched: Instant,
+    pub models: Vec<ModelInfo>,
+}
+
+impl ModelCache {
+    pub fn fresh(&self) -> Option<&[ModelInfo]> {
+        (self.fetched.elapsed() < CACHE_TTL).then_some(self.models.as_slice())
+    }
+}
+
+/// List weight files directly inside `dir`. A missing directory yields an
+/// empty list rather than an error.
+pub fn scan_dir(dir: &Path) -> Vec<ModelInfo> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut models: Vec<ModelInfo> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| WEIGHT_EXTENSIONS.contains(&ext))
+        })
+        .map(|path| {
+            let id = path
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            ModelInfo {
+                name: id.replace(['_', '-'], " "),
+                id,
+                size_mb: disk_size(&path) as f64 / (1024.0 * 1024.0),
+                path: path.display().to_string(),
+            }
+        })
+        .collect();
+    models.sort_by(|a, b| a.id.cmp(&b.id));
+    models
+}
+
+/// Size of a file, or the total size of a directory's contents.
+fn disk_size(path: &Path) -> u64 {
+    let Ok(meta) = std::fs::metadata(path) else {
+        return 0;
+    };
+    if !meta.is_dir() {
+        return meta.len();
+    }
+    std::fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| disk_size(&entry.path()))
+                .sum()
+        })
+        .unwrap_or(0)
+}
@@ -0,0 +1,71 @@
+/// macOS Input Monitoring permission.
+///
+/// Without it the NSEvent monitor installs but never sees tablet data, so
+/// native pressure silently stops working. Other platforms don't gate input
+/// this way and always report `NotRequired`.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+#[allow(dead_code)] // each platform only constructs some variants
+pub enum InputMonitoringPermission {
+    Granted,
+    Denied,
+    /// The user hasn't been asked yet.
+    Unknown,
+    NotRequired,
+}
+
+#[cfg(target_os = "macos")]
+mod ffi {
+    /// `kIOHIDRequestTypeListenEvent`
+    pub const LISTEN_EVENT: u32 = 1;
+    /// `kIOHIDAccessTypeGranted` / `kIOHIDAccessTypeDenied`
+    pub const GRANTED: u32 = 0;
+    pub const DENIED: u32 = 1;
+
+    #[link(name = "IOKit", kind = "framework")]
+    extern "C" {
+        pub fn IOHIDCheckAccess(request_type: u32) -> u32;
+        pub fn IOHIDRequestAccess(request_type: u32) -> bool;
+    }
+}
+
+/// System Settings pane for Input Monitoring.
+#[cfg(target_os = "macos")]
+const SETTINGS_URL: &str =
+    "x-apple.systempreferences:com.apple.preference.security?Privacy_ListenEvent";
+
+#[cfg(target_os = "macos")]
+pub fn check() -> InputMonitoringPermission {
+    match unsafe { ffi::IOHIDCheckAccess(ffi::LISTEN_EVENT) } {
+        ffi::GRANTED => InputMonitoringPermission::Granted,
+        ffi::DENIED => InputMonitoringPermission::Denied,
+        _ => InputMonitoringPermission::Unknown,
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn check() -> InputMonitoringPermission {
+    InputMonitoringPermission::NotRequired
+}
+
+/// Prompt for access, and open System Settings if the user already denied
+/// it (macOS only prompts once).
+#[cfg(target_os = "macos")]
+pub fn request() -> Result<InputMonitoringPermission, String> {
+    if check() == InputMonitoringPermission::Unknown {
+        unsafe { ffi::IOHIDRequestAccess(ffi::LISTEN_EVENT) };
+    }
+    let status = check();
+    if status == InputMonitoringPermission::Denied {
+        std::process::Command::new("open")
+            .arg(SETTINGS_URL)
+            .spawn()
+            .map_err(|e| format!("Failed to open System Settings: {}", e))?;
+    }
+    Ok(status)
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn request() -> Result<InputMonitoringPermission, String> {
+    Ok(InputMonitoringPermission::NotRequired)
+}
@@ -0,0 +1,77 @@
+/// Crash-safe JSON persistence.
+///
+/// Saves go through a temp file in the target's directory and a rename, so
+/// a crash mid-write leaves either the old file or the new one, never half
+/// of each. Loads never fail hard: an unreadable file is moved aside and the
+/// caller gets its defaults, so a corrupt file can't stop the app starting.
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Serialize `value` and write it to `path` atomically.
+pub fn save_json<T: Serialize>(path: &Path, value: &T) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+    write_atomic(path, json.as_bytes())
+}
+
+/// Write via a temp file in the same directory and rename it over `path`.
+/// The rename is atomic only within one filesystem, hence the same directory.
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    let dir = path
+        .parent()
+        .ok_or_else(|| format!("{} has no parent directory", path.display()))?;
+    std::fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    std::fs::write(&tmp, bytes)
+        .and_then(|_| std::fs::rename(&tmp, path))
+        .map_err(|e| {
+            let _ = std::fs::remove_file(&tmp);
+            format!("Failed to write {}: {}", path.display(), e)
+        })
+}
+
+/// Load `path`, or `T::default()` if it doesn't exist. A file that can't be
+/// parsed or fails `validate` is renamed to `<name>.corrupt-<unix secs>` so
+/// it isn't silently overwritten by the next save.
+pub fn load_json_or_default<T, F>(path: &Path, validate: F) -> T
+where
+    T: DeserializeOwned + Default,
+    F: FnOnce(&T) -> Result<(), String>,
+{
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return T::default(),
+        Err(e) => {
+            eprintln!("[persist] failed to read {}: {}", path.display(), e);
+            return T::default();
+        }
+    };
+    let parsed = serde_json::from_str::<T>(&text)
+        .map_err(|e| e.to_string())
+        .and_then(|value| validate(&value).map(|_| value));
+    match parsed {
+        Ok(value) => value,
+        Err(e) => {
+            eprintln!("[persist] {} is unusable: {}", path.display(), e);
+            back_up(path);
+            T::default()
+        }
+    }
+}
+
+fn back_up(path: &Path) {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(format!(".corrupt-{}", secs));
+    match std::fs::rename(path, &backup) {
+        Ok(()) => eprintln!("[persist] moved it to {}", Path::new(&backup).display()),
+        Err(e) => eprintln!("[persist] failed to back it up: {}", e),
+    }
+}
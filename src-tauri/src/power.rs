@@ -0,0 +1,196 @@
+/// Keeping the display awake during long runs.
+///
+/// A sleeping display can stall the GPU pipeline and interrupts watching a
+/// live loop or batch. While anything holds the wake lock, macOS gets an
+/// `IOPMAssertion` and Windows a `SetThreadExecutionState` request; other
+/// platforms report it as unsupported. Holders are named so the user's own
+/// toggle and automatic holds for batches and loop captures don't release
+/// each other's.
+use crate::state::{AppState, LockExt};
+use std::collections::BTreeSet;
+
+/// Held by `set_prevent_sleep`.
+pub const MANUAL: &str = "manual";
+/// Held while `process_batch` runs.
+pub const BATCH: &str = "batch";
+/// Held while a loop capture is active.
+pub const LOOP_CAPTURE: &str = "loop_capture";
+
+#[cfg(target_os = "macos")]
+mod ffi {
+    use std::ffi::{c_char, c_void};
+
+    pub type CFStringRef = *const c_void;
+
+    /// `kCFStringEncodingUTF8`
+    pub const UTF8: u32 = 0x0800_0100;
+    /// `kIOPMAssertionLevelOn`
+    pub const LEVEL_ON: u32 = 255;
+    /// `kIOReturnSuccess`
+    pub const SUCCESS: i32 = 0;
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        pub fn CFStringCreateWithCString(
+            alloc: *const c_void,
+            cstr: *const c_char,
+            encoding: u32,
+        ) -> CFStringRef;
+        pub fn CFRelease(cf: *const c_void);
+    }
+
+    #[link(name = "IOKit", kind = "framework")]
+    extern "C" {
+        pub fn IOPMAssertionCreateWithName(
+            assertion_type: CFStringRef,
+            level: u32,
+            name: CFStringRef,
+            id: *mut u32,
+        ) -> i32;
+        pub fn IOPMAssertionRelease(id: u32) -> i32;
+    }
+}
+
+#[cfg(windows)]
+mod ffi {
+    pub const ES_CONTINUOUS: u32 = 0x8000_0000;
+    pub const ES_SYSTEM_REQUIRED: u32 = 0x0000_0001;
+    pub const ES_DISPLAY_REQUIRED: u32 = 0x0000_0002;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        pub fn SetThreadExecutionState(flags: u32) -> u32;
+    }
+}
+
+/// The OS-level request; released on drop.
+#[cfg(target_os = "macos")]
+struct Assertion {
+    id: u32,
+}
+
+#[cfg(target_os = "macos")]
+impl Assertion {
+    fn acquire() -> Result<Self, String> {
+        let cf = |s: &std::ffi::CStr| unsafe {
+            ffi::CFStringCreateWithCString(std::ptr::null(), s.as_ptr(), ffi::UTF8)
+        };
+        // `kIOPMAssertPreventUserIdleDisplaySleep` also keeps the system up.
+        let assertion_type = cf(c"PreventUserIdleDisplaySleep");
+        let name = cf(c"Pictaflux long run");
+        let mut id = 0;
+        let result = unsafe {
+            let result =
+                ffi::IOPMAssertionCreateWithName(assertion_type, ffi::LEVEL_ON, name, &mut id);
+            ffi::CFRelease(assertion_type);
+            ffi::CFRelease(name);
+            result
+        };
+        if result != ffi::SUCCESS {
+            return Err(format!(
+                "IOPMAssertionCreateWithName failed ({:#x})",
+                result
+            ));
+        }
+        Ok(Self { id })
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl Drop for Assertion {
+    fn drop(&mut self) {
+        unsafe { ffi::IOPMAssertionRelease(self.id) };
+    }
+}
+
+/// The execution state belongs to the thread that set it, so a dedicated
+/// thread holds it until the sender is dropped.
+#[cfg(windows)]
+struct Assertion {
+    _release: std::sync::mpsc::Sender<()>,
+}
+
+#[cfg(windows)]
+impl Assertion {
+    fn acquire() -> Result<Self, String> {
+        let (release, released) = std::sync::mpsc::channel::<()>();
+        let (acquired, result) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let flags = ffi::ES_CONTINUOUS | ffi::ES_SYSTEM_REQUIRED | ffi::ES_DISPLAY_REQUIRED;
+            let ok = unsafe { ffi::SetThreadExecutionState(flags) } != 0;
+            let _ = acquired.send(ok);
+            if ok {
+                // Returns once the sender is dropped.
+                let _ = released.recv();
+                unsafe { ffi::SetThreadExecutionState(ffi::ES_CONTINUOUS) };
+            }
+        });
+        match result.recv() {
+            Ok(true) => Ok(Self { _release: release }),
+            _ => Err("SetThreadExecutionState failed".into()),
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+#[allow(dead_code)] // acquiring always fails here
+struct Assertion;
+
+#[cfg(not(any(target_os = "macos", windows)))]
+impl Assertion {
+    fn acquire() -> Result<Self, String> {
+        Err("Preventing sleep is not supported on this platform".into())
+    }
+}
+
+/// Who currently wants the display awake. The assertion is held exactly
+/// while `holders` is non-empty.
+#[derive(Default)]
+pub struct KeepAwake {
+    holders: BTreeSet<&'static str>,
+    assertion: Option<Assertion>,
+}
+
+impl KeepAwake {
+    pub fn hold(&mut self, holder: &'static str) -> Result<(), String> {
+        if self.assertion.is_none() {
+            self.assertion = Some(Assertion::acquire()?);
+        }
+        self.holders.insert(holder);
+        Ok(())
+    }
+
+    pub fn release(&mut self, holder: &'static str) {
+        self.holders.remove(holder);
+        if self.holders.is_empty() {
+            self.assertion = None;
+        }
+    }
+
+    pub fn release_all(&mut self) {
+        self.holders.clear();
+        self.assertion = None;
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.assertion.is_some()
+    }
+}
+
+/// Hold the wake lock for a long run, if the user opted into it. Failures
+/// are logged rather than failing the run.
+pub fn hold_for_run(state: &AppState, holder: &'static str) {
+    let enabled = state.settings.lock_or_recover().prevent_sleep_during_runs;
+    if !enabled {
+        return;
+    }
+    if let Err(e) = state.keep_awake.lock_or_recover().hold(holder) {
+        eprintln!("[power] {}", e);
+    }
+}
+
+/// Undo `hold_for_run`. Always releases, in case the setting changed
+/// during the run.
+pub fn release_for_run(state: &AppState, holder: &'static str) {
+    state.keep_awake.lock_or_recover().release(holder);
+}
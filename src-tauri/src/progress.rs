@@ -0,0 +1,50 @@
+/// Shared progress reporting.
+///
+/// Long-running operations (sidecar startup, batch runs, model downloads)
+/// all report through one "progress" event so the frontend renders a single
+/// progress API instead of several bespoke events. `id` is stable for the
+/// life of an operation, so the UI can group updates from concurrent ones.
+use crate::events;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tauri::AppHandle;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Serialized payload for the "progress" event.
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct Progress {
+    pub id: String,
+    pub phase: String,
+    /// 0.0 to 1.0.
+    pub fraction: f32,
+    pub message: String,
+}
+
+/// Emits `Progress` updates for one operation under a single id.
+pub struct ProgressReporter {
+    app: AppHandle,
+    id: String,
+}
+
+impl ProgressReporter {
+    /// Start a new operation; `kind` prefixes the id, e.g. "startup-3".
+    pub fn new(app: &AppHandle, kind: &str) -> Self {
+        Self {
+            app: app.clone(),
+            id: format!("{}-{}", kind, NEXT_ID.fetch_add(1, Ordering::Relaxed)),
+        }
+    }
+
+    pub fn report(&self, phase: &str, fraction: f32, message: impl Into<String>) {
+        events::emit_sidecar(
+            &self.app,
+            "progress",
+            Progress {
+                id: self.id.clone(),
+                phase: phase.to_string(),
+                fraction: fraction.clamp(0.0, 1.0),
+                message: message.into(),
+            },
+        );
+    }
+}
@@ -0,0 +1,60 @@
+//! Prompt checks before a prompt reaches the sidecar.
+//!
+//! The prompt is handed to the sidecar at startup and on into the model's
+//! text encoder. Control characters can corrupt the former, and an overlong
+//! prompt either fails in Python or is silently cut short by the latter.
+//! Both are caught here with an error or warning the user can act on.
+
+/// Tokens the CLIP text encoder reads; the rest of a prompt is ignored.
+const ENCODER_TOKENS: usize = 77;
+
+/// Rough characters per CLIP token for English prompts, used to estimate
+/// the token count without a tokenizer.
+const CHARS_PER_TOKEN: usize = 4;
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct ValidatedPrompt {
+    /// The prompt as it will be sent: trimmed, with line breaks and tabs
+    /// turned into spaces and other control characters removed.
+    pub prompt: String,
+    pub warnings: Vec<String>,
+}
+
+/// Normalize `prompt`, failing if it is longer than `max_chars`.
+pub fn validate(prompt: &str, max_chars: usize) -> Result<ValidatedPrompt, String> {
+    let mut warnings = Vec::new();
+    let mut normalized = String::with_capacity(prompt.len());
+    let (mut replaced, mut removed) = (false, false);
+    for c in prompt.chars() {
+        if matches!(c, '\n' | '\r' | '\t') {
+            normalized.push(' ');
+            replaced = true;
+        } else if c.is_control() {
+            removed = true;
+        } else {
+            normalized.push(c);
+        }
+    }
+    if replaced {
+        warnings.push("Line breaks and tabs were replaced with spaces".to_string());
+    }
+    if removed {
+        warnings.push("Control characters were removed".to_string());
+    }
+    let prompt = normalized.trim().to_string();
+
+    let chars = prompt.chars().count();
+    if chars > max_chars {
+        return Err(format!(
+            "Prompt is {} characters; the limit is {}",
+            chars, max_chars
+        ));
+    }
+    if chars > ENCODER_TOKENS * CHARS_PER_TOKEN {
+        warnings.push(format!(
+            "Prompt is likely over the model's {}-token limit; the end may be ignored",
+            ENCODER_TOKENS
+        ));
+    }
+    Ok(ValidatedPrompt { prompt, warnings })
+}
@@ -0,0 +1,103 @@
+/// Session recording and replay.
+///
+/// While recording, every tablet event and processing call is appended to a
+/// JSONL file with its offset from the start of the recording. Replaying
+/// re-emits the entries through the same event channels at their original
+/// timing, which makes input-handling bugs reproducible.
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// One line of a recording file.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct RecordedEvent {
+    /// Milliseconds since the recording started.
+    pub t_ms: u64,
+    pub event: String,
+    pub payload: serde_json::Value,
+}
+
+pub struct Recorder {
+    file: BufWriter<File>,
+    started: Instant,
+    pub count: u64,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> Result<Self, String> {
+        let file = File::create(path)
+            .map_err(|e| format!("Failed to create recording {}: {}", path.display(), e))?;
+        Ok(Self {
+            file: BufWriter::new(file),
+            started: Instant::now(),
+            count: 0,
+        })
+    }
+
+    /// Append an entry. Write failures are dropped: losing a sample is
+    /// better than disturbing the input path that's being recorded.
+    pub fn record<S: Serialize>(&mut self, event: &str, payload: &S) {
+        let Ok(payload) = serde_json::to_value(payload) else {
+            return;
+        };
+        let entry = RecordedEvent {
+            t_ms: self.started.elapsed().as_millis() as u64,
+            event: event.to_string(),
+            payload,
+        };
+        if let Ok(line) = serde_json::to_string(&entry) {
+            if writeln!(self.file, "{}", line).is_ok() {
+                self.count += 1;
+            }
+        }
+    }
+
+    pub fn finish(mut self) -> Result<u64, String> {
+        self.file
+            .flush()
+            .map_err(|e| format!("Failed to write recording: {}", e))?;
+        Ok(self.count)
+    }
+}
+
+/// Position in a recording being replayed one event at a time.
+pub struct ReplayCursor {
+    pub path: PathBuf,
+    pub entries: Vec<RecordedEvent>,
+    /// Index of the next entry to replay.
+    pub next: usize,
+}
+
+impl ReplayCursor {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            entries: load(path)?,
+            next: 0,
+        })
+    }
+
+    pub fn advance(&mut self) -> Option<&RecordedEvent> {
+        let entry = self.entries.get(self.next)?;
+        self.next += 1;
+        Some(entry)
+    }
+}
+
+/// Read a recording, failing on the first malformed line.
+pub fn load(path: &Path) -> Result<Vec<RecordedEvent>, String> {
+    let file = File::open(path)
+        .map_err(|e| format!("Failed to open recording {}: {}", path.display(), e))?;
+    BufReader::new(file)
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Ok(l) if l.trim().is_empty()))
+        .map(|(i, line)| {
+            let line = line.map_err(|e| format!("Failed to read recording: {}", e))?;
+            serde_json::from_str(&line)
+                .map_err(|e| format!("Bad recording entry on line {}: {}", i + 1, e))
+        })
+        .collect()
+}
@@ -0,0 +1,32 @@
+/// Window screenshots.
+///
+/// Tauri has no webview capture API, so on macOS this hands the window's
+/// number to `screencapture`, which captures at the display's backing scale
+/// (full Retina resolution). Other platforms report it as unsupported.
+use std::path::Path;
+use tauri::WebviewWindow;
+
+#[cfg(target_os = "macos")]
+pub async fn capture(window: &WebviewWindow, path: &Path) -> Result<(), String> {
+    use objc2_app_kit::NSWindow;
+
+    let ns_window = window.ns_window().map_err(|e| e.to_string())?;
+    // SAFETY: Tauri guarantees `ns_window` is a live NSWindow for this window.
+    let number = unsafe { &*(ns_window as *const NSWindow) }.windowNumber();
+    let status = tokio::process::Command::new("screencapture")
+        // -l: capture this window only; -o: without the drop shadow; -x: no sound
+        .args(["-l", &number.to_string(), "-o", "-x", "-t", "png"])
+        .arg(path)
+        .status()
+        .await
+        .map_err(|e| format!("Failed to run screencapture: {}", e))?;
+    if !status.success() {
+        return Err(format!("screencapture failed ({})", status));
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "macos"))]
+pub async fn capture(_window: &WebviewWindow, _path: &Path) -> Result<(), String> {
+    Err("Screenshots are only supported on macOS".into())
+}
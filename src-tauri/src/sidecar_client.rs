@@ -0,0 +1,382 @@
+/// HTTP client for the sidecar's control API.
+///
+/// Every control command (model listing and switching, cache flushes,
+/// health checks) goes through here, so endpoints, timeouts, and error
+/// mapping live in one place. Requests carry `X-Pictaflux-Api` with the API
+/// version the app speaks; a sidecar that answers with a different version
+/// is reported as `VersionMismatch` rather than as a confusing HTTP error.
+/// Sidecars that don't send the header are assumed compatible.
+///
+/// Idempotent calls are retried with backoff after transient failures
+/// (connection errors, timeouts, 502/503/504), per `ClientConfig`. Calls
+/// that change state in ways that are unsafe to repeat are sent once.
+use crate::models::ModelInfo;
+use reqwest::{Method, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Mutex;
+use std::time::Duration;
+
+pub const API_VERSION: &str = "1";
+pub(crate) const API_HEADER: &str = "X-Pictaflux-Api";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    #[error("Failed to reach sidecar: {0}")]
+    Unreachable(reqwest::Error),
+    /// The endpoint doesn't exist on this sidecar (HTTP 404 or 501).
+    #[error("Sidecar does not support {0}")]
+    Unsupported(&'static str),
+    #[error(
+        "Sidecar speaks control API version {server}, but the app needs version {API_VERSION}"
+    )]
+    VersionMismatch { server: String },
+    #[error("Sidecar returned HTTP {status} for {endpoint}")]
+    Http {
+        endpoint: &'static str,
+        status: StatusCode,
+    },
+    #[error("Bad {endpoint} response from sidecar: {source}")]
+    BadResponse {
+        endpoint: &'static str,
+        source: reqwest::Error,
+    },
+}
+
+impl ClientError {
+    /// Whether the same request might succeed if sent again.
+    fn is_transient(&self) -> bool {
+        match self {
+            ClientError::Unreachable(e) => e.is_connect() || e.is_timeout(),
+            ClientError::Http { status, .. } => matches!(
+                *status,
+                StatusCode::BAD_GATEWAY
+                    | StatusCode::SERVICE_UNAVAILABLE
+                    | StatusCode::GATEWAY_TIMEOUT
+            ),
+            _ => false,
+        }
+    }
+}
+
+/// Timeouts and retry policy for control calls; persisted as
+/// `Settings::client`.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ClientConfig {
+    /// How long to wait for the connection to open.
+    pub connect_timeout_ms: u64,
+    /// Response timeout for quick control calls. Model switches and health
+    /// checks set their own.
+    pub request_timeout_secs: u64,
+    /// Extra attempts for idempotent calls after a transient failure.
+    pub retries: u32,
+    /// Delay before the first retry; doubles after each one.
+    pub retry_backoff_ms: u64,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout_ms: 2000,
+            request_timeout_secs: 10,
+            retries: 2,
+            retry_backoff_ms: 200,
+        }
+    }
+}
+
+impl ClientConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.connect_timeout_ms == 0 {
+            return Err("client.connect_timeout_ms must be at least 1".into());
+        }
+        if self.request_timeout_secs == 0 {
+            return Err("client.request_timeout_secs must be at least 1".into());
+        }
+        if self.retries > MAX_RETRIES {
+            return Err(format!(
+                "client.retries must be at most {}, got {}",
+                MAX_RETRIES, self.retries
+            ));
+        }
+        Ok(())
+    }
+}
+
+const MAX_RETRIES: u32 = 10;
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct FlushCacheResponse {
+    /// Memory released, measured from torch's reserved (CUDA) or driver
+    /// allocated (MPS) memory; `None` without a torch GPU backend.
+    pub freed_mb: Option<f64>,
+}
+
+#[derive(serde::Serialize)]
+struct ModelRequest<'a> {
+    model: &'a str,
+}
+
+#[derive(serde::Serialize)]
+struct ModelCacheRequest<'a> {
+    path: &'a str,
+}
+
+/// Live generation parameters; unset fields are left as they are.
+#[derive(Debug, Default, serde::Serialize)]
+pub struct ParamsRequest<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub feedback: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strength: Option<f32>,
+    /// A `FeedbackMode::as_arg` value.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub feedback_mode: Option<&'a str>,
+    /// Hold the live loop's seed, or advance it every frame.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed_locked: Option<bool>,
+}
+
+/// One frame for the model. The image is the encoded frame as sent, so it
+/// is zlib-compressed PNG when `compressed` is set.
+#[derive(Debug, serde::Serialize)]
+pub struct ProcessRequest<'a> {
+    pub image_base64: String,
+    pub compressed: bool,
+    pub prompt: &'a str,
+    pub seed: u64,
+    pub regions: Vec<ProcessRegion<'a>>,
+}
+
+/// A base64 PNG mask, the size of the frame, and the prompt for its white
+/// area.
+#[derive(Debug, serde::Serialize)]
+pub struct ProcessRegion<'a> {
+    pub mask_base64: String,
+    pub prompt: &'a str,
+}
+
+#[derive(serde::Deserialize)]
+struct ProcessResponse {
+    image_base64: String,
+}
+
+/// Shared so connections to the sidecar are reused across commands; rebuilt
+/// when the connect timeout changes.
+fn http(connect_timeout: Duration) -> reqwest::Client {
+    static CLIENT: Mutex<Option<(Duration, reqwest::Client)>> = Mutex::new(None);
+    let mut cached = CLIENT.lock().unwrap_or_else(|e| e.into_inner());
+    match cached.as_ref() {
+        Some((timeout, client)) if *timeout == connect_timeout => client.clone(),
+        _ => {
+            let client = reqwest::Client::builder()
+                .connect_timeout(connect_timeout)
+                .build()
+                .unwrap_or_default();
+            *cached = Some((connect_timeout, client.clone()));
+            client
+        }
+    }
+}
+
+/// Where to reach a sidecar bound to `host`. One listening on every
+/// interface is reached over loopback.
+pub fn connect_addr(host: IpAddr, port: u16) -> SocketAddr {
+    let host = match host {
+        IpAddr::V4(v4) if v4.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        IpAddr::V6(v6) if v6.is_unspecified() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+        host => host,
+    };
+    SocketAddr::new(host, port)
+}
+
+/// The sidecar serves plain HTTP, so this is the one place its URL is
+/// spelled out.
+pub fn base_url(addr: SocketAddr) -> String {
+    format!("http://{}", addr)
+}
+
+pub struct SidecarClient {
+    base: String,
+    config: ClientConfig,
+}
+
+impl SidecarClient {
+    pub fn new(addr: SocketAddr, config: ClientConfig) -> Self {
+        Self::with_base_url(base_url(addr), config)
+    }
+
+    /// A client for the API at `base`, e.g. a mock server in tests.
+    pub fn with_base_url(base: impl Into<String>, config: ClientConfig) -> Self {
+        Self {
+            base: base.into(),
+            config,
+        }
+    }
+
+    fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.config.request_timeout_secs)
+    }
+
+    /// `send`, retried with exponential backoff while the failure is
+    /// transient. Only for requests that are safe to repeat.
+    async fn send_idempotent<B: Serialize>(
+        &self,
+        method: Method,
+        endpoint: &'static str,
+        body: Option<&B>,
+        timeout: Duration,
+    ) -> Result<Response, ClientError> {
+        let mut backoff = Duration::from_millis(self.config.retry_backoff_ms);
+        let mut attempt = 0;
+        loop {
+            match self.send(method.clone(), endpoint, body, timeout).await {
+                Err(e) if e.is_transient() && attempt < self.config.retries => {
+                    attempt += 1;
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Send a request and check its status and API version.
+    async fn send<B: Serialize>(
+        &self,
+        method: Method,
+        endpoint: &'static str,
+        body: Option<&B>,
+        timeout: Duration,
+    ) -> Result<Response, ClientError> {
+        let mut request = http(Duration::from_millis(self.config.connect_timeout_ms))
+            .request(method, format!("{}/{}", self.base, endpoint))
+            .header(API_HEADER, API_VERSION)
+            .timeout(timeout);
+        if let Some(body) = body {
+            request = request.json(body);
+        }
+        let response = request.send().await.map_err(ClientError::Unreachable)?;
+
+        if let Some(server) = response.headers().get(API_HEADER) {
+            let server = server.to_str().unwrap_or("?");
+            if server != API_VERSION {
+                return Err(ClientError::VersionMismatch {
+                    server: server.to_string(),
+                });
+            }
+        }
+        match response.status() {
+            s if s.is_success() => Ok(response),
+            StatusCode::NOT_FOUND | StatusCode::NOT_IMPLEMENTED => {
+                Err(ClientError::Unsupported(endpoint))
+            }
+            status => Err(ClientError::Http { endpoint, status }),
+        }
+    }
+
+    async fn json<B: Serialize, R: DeserializeOwned>(
+        &self,
+        method: Method,
+        endpoint: &'static str,
+        body: Option<&B>,
+        timeout: Duration,
+    ) -> Result<R, ClientError> {
+        self.send_idempotent(method, endpoint, body, timeout)
+            .await?
+            .json()
+            .await
+            .map_err(|source| ClientError::BadResponse { endpoint, source })
+    }
+
+    pub async fn health(&self, timeout: Duration) -> Result<(), ClientError> {
+        self.send_idempotent::<()>(Method::GET, "health", None, timeout)
+            .await
+            .map(|_| ())
+    }
+
+    /// Whether the sidecar answers at all: its health check passes, or it
+    /// is a sidecar without one. Anything else (no connection, a timeout,
+    /// an error status) counts as unreachable.
+    pub async fn probe(&self, timeout: Duration) -> bool {
+        matches!(
+            self.health(timeout).await,
+            Ok(()) | Err(ClientError::Unsupported(_))
+        )
+    }
+
+    /// Whether our sidecar is up: its health check passes and the reply
+    /// names our API version. Unlike `probe`, a reply without the version
+    /// header doesn't count, since any server on the port could send one.
+    pub async fn handshake(&self, timeout: Duration) -> bool {
+        self.send::<()>(Method::GET, "health", None, timeout)
+            .await
+            .is_ok_and(|response| response.headers().contains_key(API_HEADER))
+    }
+
+    pub async fn models(&self) -> Result<Vec<ModelInfo>, ClientError> {
+        self.json::<(), _>(Method::GET, "models", None, self.request_timeout())
+            .await
+    }
+
+    /// Hot-swap the loaded model. Only returns once the new model is loaded,
+    /// so it isn't retried: a timed-out load may still be running.
+    pub async fn switch_model(&self, model: &str) -> Result<(), ClientError> {
+        self.send(
+            Method::POST,
+            "model",
+            Some(&ModelRequest { model }),
+            Duration::from_secs(120),
+        )
+        .await
+        .map(|_| ())
+    }
+
+    pub async fn set_model_cache(&self, path: &str) -> Result<(), ClientError> {
+        self.send_idempotent(
+            Method::POST,
+            "model_cache",
+            Some(&ModelCacheRequest { path }),
+            self.request_timeout(),
+        )
+        .await
+        .map(|_| ())
+    }
+
+    /// Change the prompt, feedback, or strength without reloading the model.
+    pub async fn set_params(&self, params: &ParamsRequest<'_>) -> Result<(), ClientError> {
+        self.send_idempotent(Method::POST, "params", Some(params), self.request_timeout())
+            .await
+            .map(|_| ())
+    }
+
+    /// Run one frame through the model and return the result, encoded the
+    /// same way as the request's image. Sent once: a timed-out frame is
+    /// stale by the time a retry could answer.
+    pub async fn process(
+        &self,
+        request: &ProcessRequest<'_>,
+        timeout: Duration,
+    ) -> Result<String, ClientError> {
+        let response: ProcessResponse = self
+            .send(Method::POST, "process", Some(request), timeout)
+            .await?
+            .json()
+            .await
+            .map_err(|source| ClientError::BadResponse {
+                endpoint: "process",
+                source,
+            })?;
+        Ok(response.image_base64)
+    }
+
+    pub async fn flush_cache(&self) -> Result<FlushCacheResponse, ClientError> {
+        // Flushing twice is harmless, so this goes through `json`'s retries.
+        self.json::<(), _>(Method::POST, "flush_cache", None, self.request_timeout())
+            .await
+    }
+}
@@ -0,0 +1,276 @@
+/// One line of the protocol the sidecar speaks on stdout during startup.
+///
+/// Each line is either a bare keyword or `KEYWORD:payload`:
+///
+/// - `LOADING` — model loading has started.
+/// - `READY:<port>` — the server is listening on `port`.
+/// - `PROGRESS:<fraction> <message>` — `fraction` is 0.0 to 1.0; the
+///   message is optional.
+/// - `ERROR:<message>` and `WARN:<message>` — diagnostics worth surfacing
+///   beyond the log.
+/// - `METRIC:<json>` — a `Metric` object, e.g.
+///   `METRIC:{"name": "frame", "value": 53.5, "unit": "ms", "parts":
+///   [{"name": "step_0", "value": 41.5}, {"name": "vae_decode", "value": 12.0}]}`.
+///   Python sends one per frame on stderr once it is READY, so both streams
+///   are parsed for them.
+///
+/// Anything else, including a known prefix with a payload that doesn't
+/// parse, is `Unrecognized` and only ends up in the log buffer. Only a bad
+/// READY is an error, since startup can't continue without the port.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SidecarLine {
+    Loading,
+    Ready { port: u16 },
+    Progress { fraction: f32, message: String },
+    Error(String),
+    Warn(String),
+    Metric(Metric),
+    Unrecognized(String),
+}
+
+/// A measurement reported by the sidecar, such as a frame's render time.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Metric {
+    pub name: String,
+    pub value: f64,
+    /// e.g. "ms"; `None` for unitless counts.
+    #[serde(default)]
+    pub unit: Option<String>,
+    /// Breakdown of `value`, such as per-step sampler timing, in the same
+    /// unit. Sent with the total so a frame costs one line and one event.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parts: Vec<MetricPart>,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct MetricPart {
+    pub name: String,
+    pub value: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Phase {
+    Starting,
+    Loading,
+    Ready,
+}
+
+/// Parses startup output one line at a time, tracking how far the sidecar
+/// has got so out-of-order keywords are caught.
+pub struct LineParser {
+    phase: Phase,
+}
+
+impl LineParser {
+    pub fn new() -> Self {
+        Self {
+            phase: Phase::Starting,
+        }
+    }
+
+    pub fn feed(&mut self, line: &str) -> Result<SidecarLine, String> {
+        let parsed = parse_line(line)?;
+        match parsed {
+            SidecarLine::Loading if self.phase == Phase::Starting => {
+                self.phase = Phase::Loading;
+            }
+            SidecarLine::Loading => return Ok(SidecarLine::Unrecognized(line.to_string())),
+            SidecarLine::Ready { .. } if self.phase == Phase::Ready => {
+                return Err("Sidecar sent READY twice".into());
+            }
+            SidecarLine::Ready { .. } => self.phase = Phase::Ready,
+            _ => {}
+        }
+        Ok(parsed)
+    }
+}
+
+/// Classify a single line without regard to ordering.
+pub fn parse_line(line: &str) -> Result<SidecarLine, String> {
+    let line = line.trim_end();
+    if line == "LOADING" {
+        return Ok(SidecarLine::Loading);
+    }
+    let unrecognized = || SidecarLine::Unrecognized(line.to_string());
+    let Some((keyword, payload)) = line.split_once(':') else {
+        return Ok(unrecognized());
+    };
+    Ok(match keyword {
+        "READY" => SidecarLine::Ready {
+            port: payload
+                .trim()
+                .parse()
+                .map_err(|e| format!("Bad port in READY signal: {}", e))?,
+        },
+        "PROGRESS" => {
+            let payload = payload.trim_start();
+            let (fraction, message) = payload.split_once(' ').unwrap_or((payload, ""));
+            match fraction.parse::<f32>() {
+                Ok(fraction) if fraction.is_finite() => SidecarLine::Progress {
+                    fraction: fraction.clamp(0.0, 1.0),
+                    message: message.trim().to_string(),
+                },
+                _ => unrecognized(),
+            }
+        }
+        "ERROR" => SidecarLine::Error(payload.trim().to_string()),
+        "WARN" => SidecarLine::Warn(payload.trim().to_string()),
+        "METRIC" => match serde_json::from_str::<Metric>(payload) {
+            Ok(metric) if !metric.name.is_empty() => SidecarLine::Metric(metric),
+            _ => unrecognized(),
+        },
+        _ => unrecognized(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ready_carries_the_port() {
+        assert_eq!(
+            parse_line("READY:9824"),
+            Ok(SidecarLine::Ready { port: 9824 })
+        );
+        assert_eq!(
+            parse_line("READY: 9824\r\n"),
+            Ok(SidecarLine::Ready { port: 9824 })
+        );
+    }
+
+    #[test]
+    fn ready_with_a_bad_port_is_an_error() {
+        assert!(parse_line("READY:").is_err());
+        assert!(parse_line("READY:port").is_err());
+        assert!(parse_line("READY:70000").is_err());
+    }
+
+    #[test]
+    fn ready_twice_is_an_error() {
+        let mut parser = LineParser::new();
+        assert_eq!(parser.feed("LOADING"), Ok(SidecarLine::Loading));
+        assert_eq!(
+            parser.feed("READY:9824"),
+            Ok(SidecarLine::Ready { port: 9824 })
+        );
+        assert!(parser.feed("READY:9824").is_err());
+    }
+
+    #[test]
+    fn loading_after_startup_is_unrecognized() {
+        let mut parser = LineParser::new();
+        assert_eq!(parser.feed("LOADING"), Ok(SidecarLine::Loading));
+        assert_eq!(
+            parser.feed("LOADING"),
+            Ok(SidecarLine::Unrecognized("LOADING".into()))
+        );
+    }
+
+    #[test]
+    fn metric_parses_with_and_without_a_unit() {
+        assert_eq!(
+            parse_line(r#"METRIC:{"name": "step_0", "value": 41.5, "unit": "ms"}"#),
+            Ok(SidecarLine::Metric(Metric {
+                name: "step_0".into(),
+                value: 41.5,
+                unit: Some("ms".into()),
+                parts: Vec::new(),
+            }))
+        );
+        assert_eq!(
+            parse_line(r#"METRIC:{"name": "frames", "value": 3}"#),
+            Ok(SidecarLine::Metric(Metric {
+                name: "frames".into(),
+                value: 3.0,
+                unit: None,
+                parts: Vec::new(),
+            }))
+        );
+    }
+
+    #[test]
+    fn metric_parses_a_per_frame_breakdown() {
+        assert_eq!(
+            parse_line(
+                r#"METRIC:{"name": "frame", "value": 53.5, "unit": "ms", "parts": [{"name": "step_0", "value": 41.5}, {"name": "vae_decode", "value": 12.0}]}"#
+            ),
+            Ok(SidecarLine::Metric(Metric {
+                name: "frame".into(),
+                value: 53.5,
+                unit: Some("ms".into()),
+                parts: vec![
+                    MetricPart {
+                        name: "step_0".into(),
+                        value: 41.5,
+                    },
+                    MetricPart {
+                        name: "vae_decode".into(),
+                        value: 12.0,
+                    },
+                ],
+            }))
+        );
+    }
+
+    #[test]
+    fn malformed_metrics_are_unrecognized() {
+        for line in [
+            "METRIC:",
+            "METRIC:not json",
+            r#"METRIC:{"name": "", "value": 1}"#,
+            r#"METRIC:{"name": "step_0"}"#,
+        ] {
+            assert_eq!(
+                parse_line(line),
+                Ok(SidecarLine::Unrecognized(line.into())),
+                "{}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn partial_lines_are_unrecognized() {
+        for line in [
+            "READY",
+            "LOADI",
+            "PROGRESS:",
+            r#"METRIC:{"name": "step_0", "val"#,
+        ] {
+            assert_eq!(
+                parse_line(line),
+                Ok(SidecarLine::Unrecognized(line.into())),
+                "{}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn progress_is_clamped_and_keeps_its_message() {
+        assert_eq!(
+            parse_line("PROGRESS:1.5 Loading weights"),
+            Ok(SidecarLine::Progress {
+                fraction: 1.0,
+                message: "Loading weights".into(),
+            })
+        );
+        assert_eq!(
+            parse_line("PROGRESS:NaN"),
+            Ok(SidecarLine::Unrecognized("PROGRESS:NaN".into()))
+        );
+    }
+
+    #[test]
+    fn diagnostics_are_trimmed() {
+        assert_eq!(
+            parse_line("ERROR: out of memory "),
+            Ok(SidecarLine::Error("out of memory".into()))
+        );
+        assert_eq!(
+            parse_line("WARN:slow start"),
+            Ok(SidecarLine::Warn("slow start".into()))
+        );
+    }
+}
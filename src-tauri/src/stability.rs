@@ -0,0 +1,115 @@
+/// Sidecar run history for `get_stability_report`.
+///
+/// Every successful start opens a run; a stop, a crash noticed by the
+/// watchdog, or failed health checks close it. Only the most recent
+/// `MAX_RUNS` are kept for the uptime figures, but the run and crash counts
+/// cover the whole session.
+use std::collections::VecDeque;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Runs kept for the uptime statistics.
+pub const MAX_RUNS: usize = 100;
+
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunEnd {
+    Stopped,
+    Crashed,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct SidecarRun {
+    /// Milliseconds since the Unix epoch.
+    pub started_at: u64,
+    /// Unset while the run is still going.
+    pub ended_at: Option<u64>,
+    pub end: Option<RunEnd>,
+    #[serde(skip)]
+    started: Instant,
+    #[serde(skip)]
+    uptime: Option<Duration>,
+}
+
+impl SidecarRun {
+    /// How long the run lasted, or has lasted so far.
+    fn uptime(&self) -> Duration {
+        self.uptime.unwrap_or_else(|| self.started.elapsed())
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct StabilityReport {
+    pub total_runs: u64,
+    pub crashes: u64,
+    /// Over the kept runs, counting the current one so far.
+    pub mean_uptime_secs: f64,
+    pub longest_uptime_secs: f64,
+    /// The kept runs, oldest first.
+    pub runs: Vec<SidecarRun>,
+}
+
+#[derive(Default)]
+pub struct RunHistory {
+    runs: VecDeque<SidecarRun>,
+    total_runs: u64,
+    crashes: u64,
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+impl RunHistory {
+    /// Record a sidecar becoming ready. Closes a run left open as stopped.
+    pub fn start(&mut self) {
+        self.end(RunEnd::Stopped);
+        self.runs.push_back(SidecarRun {
+            started_at: now_ms(),
+            ended_at: None,
+            end: None,
+            started: Instant::now(),
+            uptime: None,
+        });
+        self.total_runs += 1;
+        while self.runs.len() > MAX_RUNS {
+            self.runs.pop_front();
+        }
+    }
+
+    /// Close the current run, if one is open. The first end wins, so a
+    /// crash followed by the cleanup stop counts as a crash.
+    pub fn end(&mut self, end: RunEnd) {
+        let Some(run) = self.runs.back_mut().filter(|run| run.end.is_none()) else {
+            return;
+        };
+        run.ended_at = Some(now_ms());
+        run.end = Some(end);
+        run.uptime = Some(run.started.elapsed());
+        if end == RunEnd::Crashed {
+            self.crashes += 1;
+        }
+    }
+
+    pub fn report(&self) -> StabilityReport {
+        let uptimes: Vec<f64> = self
+            .runs
+            .iter()
+            .map(|run| run.uptime().as_secs_f64())
+            .collect();
+        let mean_uptime_secs = if uptimes.is_empty() {
+            0.0
+        } else {
+            uptimes.iter().sum::<f64>() / uptimes.len() as f64
+        };
+        StabilityReport {
+            total_runs: self.total_runs,
+            crashes: self.crashes,
+            mean_uptime_secs,
+            longest_uptime_secs: uptimes.iter().copied().fold(0.0, f64::max),
+            runs: self.runs.iter().cloned().collect(),
+        }
+    }
+}
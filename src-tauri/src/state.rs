@@ -1,34 +1,395 @@
-use std::sync::Mutex;
+use crate::canvas::DimensionConstraints;
+use crate::config::{LaunchSettings, Settings};
+use crate::error_log::ErrorLog;
+use crate::events;
+use crate::log_buffer::LogBuffer;
+use crate::loop_capture::LoopCapture;
+use crate::models::ModelCache;
+use crate::power::KeepAwake;
+use crate::recording::{Recorder, ReplayCursor};
+use crate::sidecar_protocol::Metric;
+use crate::stability::{RunEnd, RunHistory};
+use std::collections::VecDeque;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Instant;
+use tokio::sync::Notify;
+
+/// Port the sidecar listens on unless told otherwise.
+pub const DEFAULT_PORT: u16 = 9824;
+
+/// Address the sidecar binds unless told otherwise; loopback only.
+pub const DEFAULT_BIND_HOST: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+/// Number of recent sidecar metrics kept for `get_sidecar_metrics`.
+pub const MAX_METRICS: usize = 500;
+
+/// Default for `AppState::port_release_ms`.
+pub const DEFAULT_PORT_RELEASE_MS: u64 = 2000;
+
+/// How the previous frame is blended into the next one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedbackMode {
+    /// Linear mix of the new and previous frames, weighted by `feedback`.
+    #[default]
+    Alpha,
+    Additive,
+    /// Pushes away from the previous frame, exaggerating change.
+    Difference,
+    /// Mixes in a running average of recent frames.
+    Momentum,
+}
+
+impl FeedbackMode {
+    /// The `--feedback-mode` value.
+    pub fn as_arg(self) -> &'static str {
+        match self {
+            FeedbackMode::Alpha => "alpha",
+            FeedbackMode::Additive => "additive",
+            FeedbackMode::Difference => "difference",
+            FeedbackMode::Momentum => "momentum",
+        }
+    }
+}
+
+/// What `process_canvas` asks the model for.
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProcessingMode {
+    /// Restyle the canvas.
+    #[default]
+    Img2Img,
+    /// Generate from the prompt alone; only the canvas size is used.
+    Txt2Img,
+}
+
+/// Arguments a sidecar was launched with, kept so it can be relaunched.
+#[derive(Clone, Debug)]
+pub struct LaunchArgs {
+    pub port: u16,
+    /// Address the server listens on.
+    pub bind_host: IpAddr,
+    pub prompt: String,
+    pub feedback: f32,
+    pub feedback_mode: FeedbackMode,
+    pub strength: f32,
+    pub model: String,
+    pub render_size: u16,
+    /// Working canvas size; the server renders a warm-up frame at this size
+    /// before it reports READY.
+    pub canvas_size: Option<(u32, u32)>,
+    /// Alternate sidecar script; `None` runs `diffusion_server.py`.
+    pub script: Option<String>,
+    /// Interpreter for this launch; `None` uses `Settings::python_path`, or
+    /// failing that the sidecar's `.venv`.
+    pub python_path: Option<PathBuf>,
+    /// CPU-only launch for when the GPU path is broken.
+    pub safe_mode: bool,
+}
+
+/// What the running sidecar reported once it became ready.
+#[derive(Clone, Debug)]
+pub struct ReadyInfo {
+    pub model: String,
+}
 
 pub struct SidecarState {
     pub child: Option<tokio::process::Child>,
+    /// Address the running sidecar is bound to.
+    pub host: IpAddr,
     pub port: u16,
     pub status: SidecarStatus,
+    /// Set while the sidecar is `Ready`.
+    pub ready: Option<ReadyInfo>,
+    /// Arguments of the most recent successful launch.
+    pub launch: Option<LaunchArgs>,
+    /// Settings in effect when the running sidecar was started, to tell
+    /// which later changes it hasn't picked up.
+    pub launch_settings: Option<LaunchSettings>,
+    /// Rendered command line of the most recent spawn attempt, with secrets
+    /// in the environment redacted.
+    pub last_command: String,
+    /// Set while `start_sidecar` is waiting for READY; notified by
+    /// `cancel_startup` to abort the wait.
+    pub startup_cancel: Option<Arc<Notify>>,
+    /// Last lines of the running sidecar's stderr, kept current by its
+    /// reader task.
+    pub stderr_tail: Option<Arc<Mutex<VecDeque<String>>>>,
+    /// Frames currently being rendered; the sidecar is busy while nonzero.
+    pub renders_in_flight: u32,
 }
 
-#[derive(Clone, Debug, serde::Serialize)]
-#[serde(rename_all = "lowercase")]
+/// Serializes as `{ "state": "ready", "message": null, "attempt": null }`,
+/// the same shape for every variant; `message` is set for `Error` and
+/// `attempt` for `Restarting`.
+#[derive(Clone, Debug)]
 pub enum SidecarStatus {
     Stopped,
     Loading,
     Ready,
+    /// Waiting out the backoff before auto-restart `attempt` (1-based).
+    Restarting {
+        attempt: u32,
+    },
     Error(String),
 }
 
+impl serde::Serialize for SidecarStatus {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let (state, message, attempt) = match self {
+            SidecarStatus::Stopped => ("stopped", None, None),
+            SidecarStatus::Loading => ("loading", None, None),
+            SidecarStatus::Ready => ("ready", None, None),
+            SidecarStatus::Restarting { attempt } => ("restarting", None, Some(*attempt)),
+            SidecarStatus::Error(message) => ("error", Some(message.as_str()), None),
+        };
+        let mut s = serializer.serialize_struct("SidecarStatus", 3)?;
+        s.serialize_field("state", state)?;
+        s.serialize_field("message", &message)?;
+        s.serialize_field("attempt", &attempt)?;
+        s.end()
+    }
+}
+
+/// A cancellation flag that can also be awaited. Checked between steps of a
+/// long job, and raced against the step in flight.
+pub struct CancelToken {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self {
+            cancelled: AtomicBool::new(false),
+            notify: Notify::new(),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+        self.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Resolves once `cancel` has been called.
+    pub async fn cancelled(&self) {
+        let notified = self.notify.notified();
+        if self.is_cancelled() {
+            return;
+        }
+        notified.await;
+    }
+}
+
+/// A pointing device seen entering tablet proximity.
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct TabletDevice {
+    /// NSEvent `deviceID`; shared by the device's proximity and point events.
+    pub device_id: u64,
+    pub name: String,
+    /// "pen", "eraser", "cursor", or "unknown".
+    pub pointing_type: &'static str,
+}
+
+/// Summary of the samples seen during a `ping_tablet` self-test.
+#[derive(Clone, Debug, Default, serde::Serialize)]
+pub struct TabletProbe {
+    pub samples_seen: u64,
+    pub max_pressure: f32,
+    pub had_tilt: bool,
+}
+
+/// What the native tablet monitor has learned about the connected device.
+#[derive(Clone, Debug, Default, serde::Serialize)]
+pub struct TabletInfo {
+    pub device_name: Option<String>,
+    /// Estimated pressure resolution; 0 until enough samples arrive.
+    pub pressure_levels: u32,
+    /// Every device seen this session, in order of first appearance.
+    pub devices: Vec<TabletDevice>,
+    /// When set, only samples from this device are emitted.
+    pub active_device: Option<u64>,
+}
+
 pub struct AppState {
+    pub settings: Mutex<Settings>,
     pub last_prompt: Mutex<String>,
+    /// Used by starts that don't pass a feedback mode.
+    pub feedback_mode: Mutex<FeedbackMode>,
+    /// Live-loop strength keyframes as (frame, strength), ascending by
+    /// frame; `None` while the static strength applies.
+    pub strength_schedule: Mutex<Option<Vec<(u32, f32)>>>,
+    /// Set by `set_mode`; applies to every render.
+    pub mode: Mutex<ProcessingMode>,
     pub sidecar: Mutex<SidecarState>,
+    /// Set once the sidecar is being stopped because the app is closing.
+    pub exit_shutdown: AtomicBool,
+    /// Notified when a sidecar's stderr closes, which happens as it exits,
+    /// to wake the crash watchdog.
+    pub sidecar_output_closed: Notify,
+    /// Recent sidecar output; shared with the stderr reader task.
+    pub logs: Arc<Mutex<LogBuffer>>,
+    /// Most recent METRIC reports, oldest first, at most `MAX_METRICS`.
+    pub metrics: Mutex<VecDeque<Metric>>,
+    pub dimensions: Mutex<DimensionConstraints>,
+    /// Last time a processing or update command ran, for the idle auto-stop.
+    pub last_activity: Mutex<Instant>,
+    /// Stop the sidecar after this many idle seconds; 0 disables.
+    pub idle_secs: AtomicU64,
+    /// Zlib-compress frames sent to the sidecar.
+    pub compress_frames: AtomicBool,
+    /// Throttles the live loop and caps the render size; see `low_power`.
+    pub low_power: AtomicBool,
+    /// Retries after a failed start; 0 disables auto-restart.
+    pub auto_restart_attempts: AtomicU32,
+    /// How long to wait for the port to free up after orphan cleanup.
+    pub port_release_ms: AtomicU64,
+    /// Directory scanned for models while the sidecar isn't running; `None`
+    /// means the default `sidecar/models`.
+    pub models_dir: Mutex<Option<PathBuf>>,
+    pub model_cache: Mutex<Option<ModelCache>>,
+    /// Shared by in-flight `process_canvas` calls; `clear_canvas` trips it and
+    /// installs a fresh one.
+    pub canvas_cancel: Mutex<Arc<CancelToken>>,
+    /// Id of the newest `process_canvas` call dispatched to the sidecar.
+    pub frame_generation: AtomicU64,
+    /// Id of the newest frame returned by `process_canvas`; results from
+    /// older calls are dropped so a straggler can't replace a newer frame.
+    pub frame_delivered: AtomicU64,
+    /// While set, frames without an explicit seed reuse `seed`. Locked by
+    /// default, like the sidecar's live loop.
+    pub seed_locked: AtomicBool,
+    /// Seed of the latest `process_canvas` or batch frame.
+    pub seed: AtomicU64,
+    /// Set while `process_batch` runs; tripped by `cancel_batch`.
+    pub batch_cancel: Mutex<Option<Arc<CancelToken>>>,
+    /// Active session recording, if any.
+    pub recorder: Mutex<Option<Recorder>>,
+    /// Active live-loop capture, if any.
+    pub loop_capture: Mutex<Option<LoopCapture>>,
+    /// Recording being stepped through by `replay_step`.
+    pub replay: Mutex<Option<ReplayCursor>>,
+    pub tablet: Mutex<TabletInfo>,
+    /// Set while `ping_tablet` is collecting samples.
+    pub tablet_probe: Mutex<Option<TabletProbe>>,
+    /// Coalesce tablet samples into one "native-tablet-batch" event per
+    /// frame instead of emitting each one.
+    pub tablet_batch: AtomicBool,
+    /// Label of the window that receives native tablet events.
+    pub tablet_target: Mutex<String>,
+    /// Drop tablet samples while the target window isn't focused.
+    pub tablet_focus_gating: AtomicBool,
+    /// Images opened before the frontend was listening; `None` once
+    /// `take_pending_images` has collected them.
+    pub pending_images: Mutex<Option<Vec<String>>>,
+    /// Label of the focused window, tracked from window focus events.
+    pub focused_window: Mutex<Option<String>>,
+    /// Display sleep prevention and who is holding it.
+    pub keep_awake: Mutex<KeepAwake>,
+    /// Recent command failures and crashes, for `get_recent_errors`.
+    pub errors: Mutex<ErrorLog>,
+    /// Sidecar runs this session, for `get_stability_report`.
+    pub runs: Mutex<RunHistory>,
 }
 
 impl AppState {
     pub fn new() -> Self {
         Self {
+            settings: Mutex::new(Settings::default()),
             last_prompt: Mutex::new(String::new()),
+            feedback_mode: Mutex::new(FeedbackMode::default()),
+            strength_schedule: Mutex::new(None),
+            mode: Mutex::new(ProcessingMode::default()),
             sidecar: Mutex::new(SidecarState {
                 child: None,
-                port: 9824,
+                host: DEFAULT_BIND_HOST,
+                port: DEFAULT_PORT,
                 status: SidecarStatus::Stopped,
+                ready: None,
+                launch: None,
+                launch_settings: None,
+                last_command: String::new(),
+                startup_cancel: None,
+                stderr_tail: None,
+                renders_in_flight: 0,
             }),
+            exit_shutdown: AtomicBool::new(false),
+            sidecar_output_closed: Notify::new(),
+            logs: Arc::new(Mutex::new(LogBuffer::new())),
+            metrics: Mutex::new(VecDeque::new()),
+            dimensions: Mutex::new(DimensionConstraints::default()),
+            last_activity: Mutex::new(Instant::now()),
+            idle_secs: AtomicU64::new(0),
+            compress_frames: AtomicBool::new(false),
+            low_power: AtomicBool::new(false),
+            auto_restart_attempts: AtomicU32::new(0),
+            port_release_ms: AtomicU64::new(DEFAULT_PORT_RELEASE_MS),
+            models_dir: Mutex::new(None),
+            model_cache: Mutex::new(None),
+            canvas_cancel: Mutex::new(Arc::new(CancelToken::new())),
+            frame_generation: AtomicU64::new(0),
+            frame_delivered: AtomicU64::new(0),
+            seed_locked: AtomicBool::new(true),
+            seed: AtomicU64::new(0),
+            batch_cancel: Mutex::new(None),
+            recorder: Mutex::new(None),
+            replay: Mutex::new(None),
+            loop_capture: Mutex::new(None),
+            tablet: Mutex::new(TabletInfo::default()),
+            tablet_probe: Mutex::new(None),
+            tablet_batch: AtomicBool::new(false),
+            tablet_target: Mutex::new(events::MAIN_WINDOW.to_string()),
+            tablet_focus_gating: AtomicBool::new(true),
+            pending_images: Mutex::new(Some(Vec::new())),
+            // The main window opens focused.
+            focused_window: Mutex::new(Some(events::MAIN_WINDOW.to_string())),
+            keep_awake: Mutex::new(KeepAwake::default()),
+            errors: Mutex::new(ErrorLog::default()),
+            runs: Mutex::new(RunHistory::default()),
         }
     }
+
+    /// Add a failure to the error history.
+    pub fn record_error(&self, source: &str, message: impl std::fmt::Display) {
+        self.errors
+            .lock_or_recover()
+            .push(source, message.to_string());
+    }
+
+    /// Close the current sidecar run in the stability history.
+    pub fn end_run(&self, end: RunEnd) {
+        self.runs.lock_or_recover().end(end);
+    }
+
+    /// Reset the idle timer.
+    pub fn touch_activity(&self) {
+        *self.last_activity.lock_or_recover() = Instant::now();
+    }
+}
+
+/// Locking that survives a panic in another holder. A panic while a lock
+/// is held poisons it, and with `lock().map_err(..)?` every later command
+/// touching that state would fail for the rest of the session. The state
+/// here is plain data that stays usable after an interrupted update, so
+/// the poison is cleared and logged instead.
+pub trait LockExt<T> {
+    fn lock_or_recover(&self) -> MutexGuard<'_, T>;
+}
+
+impl<T> LockExt<T> for Mutex<T> {
+    fn lock_or_recover(&self) -> MutexGuard<'_, T> {
+        self.lock().unwrap_or_else(|poisoned| {
+            eprintln!("[state] Recovered a lock poisoned by an earlier panic");
+            self.clear_poison();
+            poisoned.into_inner()
+        })
+    }
 }
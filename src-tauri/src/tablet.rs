@@ -3,12 +3,58 @@
 /// WKWebView does not forward Wacom pen pressure through PointerEvent.
 /// We hook into NSEvent directly and emit the real pressure/tilt values
 /// to the frontend via a Tauri event.
+use crate::commands::apply_device_pressure_profile;
+use crate::events;
+use crate::state::{AppState, LockExt, TabletDevice};
 use block2::RcBlock;
 use objc2::rc::Retained;
 use objc2::runtime::AnyObject;
-use objc2_app_kit::{NSEvent, NSEventMask};
+use objc2::MainThreadMarker;
+use objc2_app_kit::{NSEvent, NSEventMask, NSEventSubtype, NSEventType, NSPointingDeviceType};
+use objc2_foundation::{NSPoint, NSRect};
+use std::cell::Cell;
 use std::ptr::NonNull;
-use tauri::{AppHandle, Emitter};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+
+/// How often batched samples are flushed, roughly one display frame.
+const BATCH_INTERVAL: Duration = Duration::from_millis(16);
+
+/// Default cap on "native-tablet" events per second. High-rate tablets
+/// report several hundred points a second, more than the IPC bridge keeps
+/// up with.
+pub const DEFAULT_MAX_RATE_HZ: u32 = 120;
+
+/// Minimum gap between "tablet-range" events.
+const RANGE_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, thiserror::Error)]
+pub enum TabletError {
+    /// AppKit refused to install the event monitor.
+    #[error("Could not install the NSEvent tablet monitor. Check that Pictaflux has Input Monitoring permission in System Settings > Privacy & Security.")]
+    MonitorUnavailable,
+}
+
+/// Serialized payload for the "tablet-init-failed" event.
+#[derive(Clone, serde::Serialize)]
+struct TabletInitFailedPayload {
+    message: String,
+}
+
+/// Log a monitor startup failure and tell the frontend, which otherwise has
+/// no way to know native pressure is unavailable.
+pub fn report_init_failure(app: &AppHandle, error: &TabletError) {
+    eprintln!("[tablet] {}", error);
+    events::emit_tablet(
+        app,
+        "tablet-init-failed",
+        TabletInitFailedPayload {
+            message: error.to_string(),
+        },
+    );
+}
 
 /// Serialized payload for the "native-tablet" event.
 #[derive(Clone, serde::Serialize)]
@@ -16,49 +62,691 @@ struct TabletPayload {
     pressure: f32,
     tilt_x: f64,
     tilt_y: f64,
+    /// Pen position on the tablet surface, in device units rather than
+    /// pixels; the range depends on the tablet model. Lets the frontend map
+    /// the tablet's active area 1:1 onto the canvas.
+    absolute_x: i64,
+    absolute_y: i64,
+    /// Position in the window's content area, in physical pixels from its
+    /// top-left corner like the canvas backing store. `None` for events
+    /// outside any window.
+    x: Option<f64>,
+    y: Option<f64>,
+    /// "pen" for tablet samples, "trackpad" for Force Touch pressure (which
+    /// has no tilt or absolute position).
+    pointing_device: &'static str,
+    /// False while the pen hovers in proximity without touching, so the UI
+    /// can show a cursor instead of starting a stroke.
+    in_contact: bool,
+    /// Barrel buttons held, as NSEvent's button mask: bit 0 is the tip,
+    /// bit 1 the lower barrel button, bit 2 the upper.
+    buttons: u32,
+    /// The eraser end of the stylus is in use.
+    is_eraser: bool,
+    /// False for the sample sent when the pen leaves proximity, so the
+    /// frontend can end any stroke in progress and ignore stray points.
+    in_proximity: bool,
+}
+
+/// Coalesces unbatched samples to the configured rate.
+struct Throttle {
+    min_interval: Duration,
+    last_emit: Option<Instant>,
+    /// The newest sample held back since the last emit. It goes out once
+    /// the interval is up, so the pen's latest position is never lost.
+    held: Option<TabletPayload>,
+}
+
+impl Throttle {
+    fn new(min_interval: Duration) -> Self {
+        Self {
+            min_interval,
+            last_emit: None,
+            held: None,
+        }
+    }
+
+    /// The samples to emit now, in order, for a new `payload`. A boundary
+    /// (contact or proximity changing) always goes out, after any held
+    /// sample; otherwise `payload` goes out if it is due and is held if not.
+    fn offer(
+        &mut self,
+        payload: TabletPayload,
+        boundary: bool,
+        now: Instant,
+    ) -> [Option<TabletPayload>; 2] {
+        if boundary {
+            self.last_emit = Some(now);
+            [self.held.take(), Some(payload)]
+        } else if self.is_due(now) {
+            self.last_emit = Some(now);
+            self.held = None;
+            [Some(payload), None]
+        } else {
+            self.held = Some(payload);
+            [None, None]
+        }
+    }
+
+    /// The held sample, once it is due.
+    fn flush(&mut self, now: Instant) -> Option<TabletPayload> {
+        if self.held.is_none() || !self.is_due(now) {
+            return None;
+        }
+        self.last_emit = Some(now);
+        self.held.take()
+    }
+
+    fn is_due(&self, now: Instant) -> bool {
+        self.last_emit
+            .is_none_or(|last| now - last >= self.min_interval)
+    }
+}
+
+/// Once per frame, drain batched samples into a "native-tablet-batch"
+/// event and send the sample `throttle` held back, if it is due.
+fn spawn_batch_flusher(
+    app: AppHandle,
+    batch: Arc<Mutex<Vec<TabletPayload>>>,
+    throttle: Arc<Mutex<Throttle>>,
+) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(BATCH_INTERVAL);
+        loop {
+            interval.tick().await;
+            let held = throttle.lock_or_recover().flush(Instant::now());
+            if let Some(payload) = held {
+                events::emit_tablet(&app, "native-tablet", payload);
+            }
+            let samples = {
+                let mut batch = batch.lock_or_recover();
+                if batch.is_empty() {
+                    continue;
+                }
+                std::mem::take(&mut *batch)
+            };
+            events::emit_tablet(&app, "native-tablet-batch", samples);
+        }
+    });
+}
+
+/// Serialized payload for the "tablet-connected" event.
+#[derive(Clone, serde::Serialize)]
+struct TabletConnectedPayload {
+    device_name: String,
+    /// 0 if this device hasn't been seen long enough to estimate it.
+    pressure_levels: u32,
+}
+
+/// Estimates a device's pressure resolution from its samples.
+///
+/// NSEvent doesn't report how many pressure levels a tablet has, but
+/// pressure arrives quantized to `1 / (levels - 1)`, so the smallest step
+/// between consecutive distinct readings reveals it.
+struct PressureEstimator {
+    last: Cell<f32>,
+    min_step: Cell<f32>,
+}
+
+impl PressureEstimator {
+    fn new() -> Self {
+        Self {
+            last: Cell::new(0.0),
+            min_step: Cell::new(1.0),
+        }
+    }
+
+    fn reset(&self) {
+        self.last.set(0.0);
+        self.min_step.set(1.0);
+    }
+
+    /// Feed a sample; returns the estimated level count once it is
+    /// plausible, snapped to the nearest power of two.
+    fn observe(&self, pressure: f32) -> Option<u32> {
+        let step = (pressure - self.last.replace(pressure)).abs();
+        if step > 0.0 && step < self.min_step.get() {
+            self.min_step.set(step);
+        }
+        let levels = 1.0 / self.min_step.get() + 1.0;
+        // Fewer than 256 levels usually just means we haven't seen a slow
+        // enough stroke yet.
+        (levels >= 256.0).then(|| 2u32.pow(levels.log2().round() as u32).min(16384))
+    }
+}
+
+/// Serialized payload for the "tablet-range" event.
+#[derive(Clone, serde::Serialize)]
+struct TabletRangePayload {
+    pressure_min: f32,
+    pressure_max: f32,
+}
+
+/// Running min/max of raw pressure since the pen entered proximity, for the
+/// frontend's pressure meter.
+struct PressureRange {
+    min: Cell<f32>,
+    max: Cell<f32>,
+    last_emit: Cell<Option<Instant>>,
+}
+
+impl PressureRange {
+    fn new() -> Self {
+        Self {
+            min: Cell::new(f32::MAX),
+            max: Cell::new(0.0),
+            last_emit: Cell::new(None),
+        }
+    }
+
+    fn reset(&self) {
+        self.min.set(f32::MAX);
+        self.max.set(0.0);
+        self.last_emit.set(None);
+    }
+
+    /// Feed a sample; returns the range when it's time for another event.
+    /// Zero pressure is the pen hovering, not the low end of the range.
+    fn observe(&self, pressure: f32) -> Option<TabletRangePayload> {
+        if pressure <= 0.0 {
+            return None;
+        }
+        self.min.set(self.min.get().min(pressure));
+        self.max.set(self.max.get().max(pressure));
+        let now = Instant::now();
+        if self.last_emit.get().is_some_and(|t| now - t < RANGE_INTERVAL) {
+            return None;
+        }
+        self.last_emit.set(Some(now));
+        Some(TabletRangePayload {
+            pressure_min: self.min.get(),
+            pressure_max: self.max.get(),
+        })
+    }
+}
+
+fn pointing_type_name(kind: NSPointingDeviceType) -> &'static str {
+    match kind {
+        NSPointingDeviceType::Pen => "pen",
+        NSPointingDeviceType::Eraser => "eraser",
+        NSPointingDeviceType::Cursor => "cursor",
+        _ => "unknown",
+    }
+}
+
+/// Whether a sample from `device_id` passes the `set_active_tablet_device`
+/// filter. Samples with no device (trackpad pressure) are dropped while a
+/// device is selected.
+fn device_allowed(app: &AppHandle, device_id: Option<u64>) -> bool {
+    let active = app
+        .state::<AppState>()
+        .tablet
+        .lock_or_recover()
+        .active_device;
+    active.is_none() || active == device_id
+}
+
+/// Whether samples may be emitted given window focus. Focus is read from
+/// state kept current by window focus events, since asking the window from
+/// inside the event monitor would re-enter the event loop.
+fn focus_allows(app: &AppHandle) -> bool {
+    let state = app.state::<AppState>();
+    if !state.tablet_focus_gating.load(Ordering::Relaxed) {
+        return true;
+    }
+    let focused = state.focused_window.lock_or_recover();
+    let target = state.tablet_target.lock_or_recover();
+    focused.as_deref() == Some(target.as_str())
+}
+
+fn vendor_name(vendor_id: usize) -> &'static str {
+    match vendor_id {
+        0x056a => "Wacom",
+        0x28bd => "XP-Pen",
+        0x256c => "Huion",
+        _ => "Tablet",
+    }
+}
+
+/// Record the device and, if it passes the device filter, reset or apply its
+/// pressure state. Filtered devices are still listed so they can be picked.
+/// Returns whether the device passed the filter.
+fn handle_proximity(
+    app: &AppHandle,
+    ev: &NSEvent,
+    estimator: &PressureEstimator,
+    range: &PressureRange,
+) -> bool {
+    let device_id = ev.deviceID() as u64;
+    let allowed = device_allowed(app, Some(device_id));
+    if !ev.isEnteringProximity() {
+        if allowed {
+            range.reset();
+        }
+        return allowed;
+    }
+    let device_name = format!("{} {:#06x}", vendor_name(ev.vendorID()), ev.tabletID());
+
+    let state = app.state::<AppState>();
+    let mut device_changed = false;
+    let pressure_levels = {
+        let mut tablet = state.tablet.lock_or_recover();
+        if !tablet.devices.iter().any(|d| d.device_id == device_id) {
+            tablet.devices.push(TabletDevice {
+                device_id,
+                name: device_name.clone(),
+                pointing_type: pointing_type_name(ev.pointingDeviceType()),
+            });
+        }
+        if !allowed {
+            return false;
+        }
+        if tablet.device_name.as_deref() != Some(device_name.as_str()) {
+            tablet.device_name = Some(device_name.clone());
+            tablet.pressure_levels = 0;
+            estimator.reset();
+            device_changed = true;
+        }
+        tablet.pressure_levels
+    };
+    if device_changed {
+        apply_device_pressure_profile(app, &device_name);
+    }
+
+    events::emit_tablet(
+        app,
+        "tablet-connected",
+        TabletConnectedPayload {
+            device_name,
+            pressure_levels,
+        },
+    );
+    true
+}
+
+/// Map a point in AppKit screen coordinates (points, origin at the bottom
+/// left) to physical pixels in a window whose content area is
+/// `window_frame`, origin at its top left. `scale` is the window's backing
+/// scale factor; without it strokes land at half or double position on
+/// HiDPI displays.
+pub fn screen_to_canvas(point: NSPoint, window_frame: NSRect, scale: f64) -> (f64, f64) {
+    (
+        (point.x - window_frame.origin.x) * scale,
+        (window_frame.origin.y + window_frame.size.height - point.y) * scale,
+    )
+}
+
+/// Where `ev` happened in its window's canvas. The scale comes from the
+/// event's own window, which may not be the tablet target window or may
+/// have just moved to another display.
+fn canvas_location(ev: &NSEvent) -> Option<(f64, f64)> {
+    let window = ev.window(MainThreadMarker::new()?)?;
+    let content = window.contentRectForFrameRect(window.frame());
+    let point = window.convertPointToScreen(ev.locationInWindow());
+    let scale = window.backingScaleFactor();
+    Some(screen_to_canvas(point, content, scale))
+}
+
+/// Some drivers occasionally report NaN, infinite, or out-of-range
+/// readings, which would otherwise reach the brush math. Pressure is kept
+/// to 0–1 and non-finite values read as 0.
+fn sanitize_pressure(pressure: f32) -> f32 {
+    if pressure.is_finite() {
+        pressure.clamp(0.0, 1.0)
+    } else {
+        0.0
+    }
+}
+
+/// Like `sanitize_pressure`, for a tilt axis in -1–1.
+fn sanitize_tilt(tilt: f64) -> f64 {
+    if tilt.is_finite() {
+        tilt.clamp(-1.0, 1.0)
+    } else {
+        0.0
+    }
+}
+
+/// Map Force Touch pressure onto 0–1. `pressure` restarts from 0 in each
+/// stage (1 = normal click, 2 = deep press), so the two stages are laid end
+/// to end.
+fn force_touch_pressure(stage: isize, pressure: f32) -> f32 {
+    match stage {
+        s if s <= 0 => 0.0,
+        1 => pressure * 0.5,
+        _ => 0.5 + pressure * 0.5,
+    }
+    .clamp(0.0, 1.0)
+}
+
+/// Whether `subtype()` is defined for events of `event_type`. Of the events
+/// the monitor receives, only the mouse and tablet ones have a subtype;
+/// asking a Force Touch pressure event for one raises an exception.
+fn has_subtype(event_type: NSEventType) -> bool {
+    [
+        NSEventType::LeftMouseDown,
+        NSEventType::LeftMouseUp,
+        NSEventType::LeftMouseDragged,
+        NSEventType::MouseMoved,
+        NSEventType::TabletPoint,
+        NSEventType::TabletProximity,
+    ]
+    .contains(&event_type)
+}
+
+/// The monitor block's return value. Returning null would consume the event,
+/// and AppKit plays the system alert sound for some consumed mouse events,
+/// so the monitor never consumes anything: mouse down/up/dragged/moved,
+/// tablet point, tablet proximity, and trackpad pressure events are all read
+/// and then handed back untouched. Taking `NonNull` makes a null return impossible here;
+/// any future consumption must be an explicit, per-type decision.
+fn pass_through(event: NonNull<NSEvent>) -> *mut NSEvent {
+    event.as_ptr()
 }
 
 /// Start monitoring NSEvent for tablet point data.
 /// Must be called on the main thread (Tauri setup runs on main).
-pub fn start_tablet_monitor(app: AppHandle) {
+///
+/// Unbatched samples are coalesced to at most `max_rate_hz` events a second
+/// (0 sends every one): of the samples arriving sooner than that after the
+/// last emit, only the newest is kept and sent when the interval is up.
+/// Samples that start or end contact or proximity always go out at once.
+pub fn start_tablet_monitor(app: AppHandle, max_rate_hz: u32) -> Result<(), TabletError> {
     let mask = NSEventMask::LeftMouseDown
         | NSEventMask::LeftMouseUp
         | NSEventMask::LeftMouseDragged
         | NSEventMask::MouseMoved
-        | NSEventMask::TabletPoint;
+        | NSEventMask::TabletPoint
+        | NSEventMask::TabletProximity
+        | NSEventMask::Pressure;
+
+    let estimator = PressureEstimator::new();
+    let range = PressureRange::new();
+    // Which end of the pen is in use is only reported on proximity events,
+    // so it is remembered for the points that follow.
+    let eraser = Cell::new(false);
+    let batch = Arc::new(Mutex::new(Vec::new()));
+    let min_interval = match max_rate_hz {
+        0 => Duration::ZERO,
+        hz => Duration::from_secs(1) / hz,
+    };
+    let throttle = Arc::new(Mutex::new(Throttle::new(min_interval)));
+    let (flush_app, flush_batch, flush_throttle) = (app.clone(), batch.clone(), throttle.clone());
+    let last_contact = Cell::new(false);
+
+    let emit_sample = move |app: &AppHandle, payload: TabletPayload| {
+        if !focus_allows(app) {
+            return;
+        }
+        if let Some(probe) = app
+            .state::<AppState>()
+            .tablet_probe
+            .lock_or_recover()
+            .as_mut()
+        {
+            probe.samples_seen += 1;
+            probe.max_pressure = probe.max_pressure.max(payload.pressure);
+            probe.had_tilt |= payload.tilt_x != 0.0 || payload.tilt_y != 0.0;
+            eprintln!(
+                "[tablet] ping: {} pressure={:.3} tilt=({:.2}, {:.2})",
+                payload.pointing_device, payload.pressure, payload.tilt_x, payload.tilt_y
+            );
+        }
+        if app.state::<AppState>().tablet_batch.load(Ordering::Relaxed) {
+            batch.lock_or_recover().push(payload);
+        } else {
+            // Pen down and up (pressure reaching zero) and leaving
+            // proximity always get through.
+            let boundary = payload.in_contact != last_contact.replace(payload.in_contact)
+                || !payload.in_proximity;
+            let samples = throttle
+                .lock_or_recover()
+                .offer(payload, boundary, Instant::now());
+            for payload in samples.into_iter().flatten() {
+                events::emit_tablet(app, "native-tablet", payload);
+            }
+        }
+    };
 
     let block = RcBlock::new(move |event: NonNull<NSEvent>| -> *mut NSEvent {
         let ev = unsafe { event.as_ref() };
 
-        // subtype 1 = NSTabletPointEventSubtype
-        // subtype() can panic for certain event types, so guard with catch
-        let is_tablet = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-            ev.subtype().0 == 1
-        }))
-        .unwrap_or(false);
+        // Accessors are only called on the event types that define them:
+        // AppKit raises an Objective-C exception otherwise, which unwinding
+        // can't catch.
+        let event_type = ev.r#type();
+        let subtype = has_subtype(event_type).then(|| ev.subtype());
+        let is_tablet = subtype == Some(NSEventSubtype::TabletPoint);
 
-        if is_tablet {
-            let pressure = ev.pressure();
+        if event_type == NSEventType::TabletProximity
+            || subtype == Some(NSEventSubtype::TabletProximity)
+        {
+            if !handle_proximity(&app, ev, &estimator, &range) {
+                return pass_through(event);
+            }
+            let entering = ev.isEnteringProximity();
+            let kind = ev.pointingDeviceType();
+            eraser.set(entering && kind == NSPointingDeviceType::Eraser);
+            let location = canvas_location(ev);
+            emit_sample(&app, TabletPayload {
+                pressure: 0.0,
+                tilt_x: 0.0,
+                tilt_y: 0.0,
+                absolute_x: 0,
+                absolute_y: 0,
+                x: location.map(|(x, _)| x),
+                y: location.map(|(_, y)| y),
+                pointing_device: "pen",
+                in_contact: false,
+                buttons: 0,
+                is_eraser: eraser.get(),
+                in_proximity: entering,
+            });
+        }
+
+        if is_tablet && device_allowed(&app, Some(ev.deviceID() as u64)) {
+            let pressure = sanitize_pressure(ev.pressure());
             let tilt = ev.tilt();
+            let (absolute_x, absolute_y) = (ev.absoluteX() as i64, ev.absoluteY() as i64);
+            let buttons = ev.buttonMask().0 as u32;
 
-            let _ = app.emit(
-                "native-tablet",
-                TabletPayload {
-                    pressure,
-                    tilt_x: tilt.x,
-                    tilt_y: tilt.y,
-                },
-            );
+            if let Some(levels) = estimator.observe(pressure) {
+                app.state::<AppState>()
+                    .tablet
+                    .lock_or_recover()
+                    .pressure_levels = levels;
+            }
+            if let Some(payload) = range.observe(pressure) {
+                events::emit_tablet(&app, "tablet-range", payload);
+            }
+            let location = canvas_location(ev);
+            // Hover arrives as mouse-moved; drags and bare tablet points
+            // with pressure mean the tip is down.
+            let in_contact = match event_type {
+                NSEventType::LeftMouseDown | NSEventType::LeftMouseDragged => true,
+                NSEventType::TabletPoint => pressure > 0.0,
+                _ => false,
+            };
+
+            emit_sample(&app, TabletPayload {
+                pressure,
+                tilt_x: sanitize_tilt(tilt.x),
+                tilt_y: sanitize_tilt(tilt.y),
+                absolute_x,
+                absolute_y,
+                x: location.map(|(x, _)| x),
+                y: location.map(|(_, y)| y),
+                pointing_device: "pen",
+                in_contact,
+                buttons,
+                is_eraser: eraser.get(),
+                in_proximity: true,
+            });
+        } else if event_type == NSEventType::Pressure && device_allowed(&app, None) {
+            // Force Touch trackpad: no tablet, but still variable pressure.
+            // Stage 0 is a resting finger that hasn't clicked.
+            let stage = ev.stage();
+            let location = canvas_location(ev);
+            emit_sample(&app, TabletPayload {
+                pressure: force_touch_pressure(stage, sanitize_pressure(ev.pressure())),
+                tilt_x: 0.0,
+                tilt_y: 0.0,
+                absolute_x: 0,
+                absolute_y: 0,
+                x: location.map(|(x, _)| x),
+                y: location.map(|(_, y)| y),
+                pointing_device: "trackpad",
+                in_contact: stage > 0,
+                buttons: 0,
+                is_eraser: false,
+                in_proximity: true,
+            });
         }
 
-        event.as_ptr()
+        pass_through(event)
     });
 
-    let _monitor: Option<Retained<AnyObject>> = unsafe {
+    let monitor: Option<Retained<AnyObject>> = unsafe {
         NSEvent::addLocalMonitorForEventsMatchingMask_handler(mask, &block)
     };
+    let monitor = monitor.ok_or(TabletError::MonitorUnavailable)?;
+    spawn_batch_flusher(flush_app, flush_batch, flush_throttle);
 
     // Leak the monitor — it must stay alive for the lifetime of the app.
     // The block is moved into the monitor, so leaking the monitor keeps both alive.
-    std::mem::forget(_monitor);
+    std::mem::forget(monitor);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use objc2_foundation::NSSize;
+
+    /// A 400x300 content area whose bottom left is at (100, 50) on screen.
+    fn content() -> NSRect {
+        NSRect::new(NSPoint::new(100.0, 50.0), NSSize::new(400.0, 300.0))
+    }
+
+    #[test]
+    fn screen_to_canvas_flips_y_to_a_top_left_origin() {
+        // Bottom left of the content area.
+        assert_eq!(
+            screen_to_canvas(NSPoint::new(100.0, 50.0), content(), 1.0),
+            (0.0, 300.0)
+        );
+        // Top left.
+        assert_eq!(
+            screen_to_canvas(NSPoint::new(100.0, 350.0), content(), 1.0),
+            (0.0, 0.0)
+        );
+        assert_eq!(
+            screen_to_canvas(NSPoint::new(150.0, 330.0), content(), 1.0),
+            (50.0, 20.0)
+        );
+    }
+
+    #[test]
+    fn screen_to_canvas_scales_to_physical_pixels() {
+        assert_eq!(
+            screen_to_canvas(NSPoint::new(150.0, 330.0), content(), 2.0),
+            (100.0, 40.0)
+        );
+        assert_eq!(
+            screen_to_canvas(NSPoint::new(500.0, 50.0), content(), 2.0),
+            (800.0, 600.0)
+        );
+    }
+
+    fn sample(pressure: f32) -> TabletPayload {
+        TabletPayload {
+            pressure,
+            tilt_x: 0.0,
+            tilt_y: 0.0,
+            absolute_x: 0,
+            absolute_y: 0,
+            x: None,
+            y: None,
+            pointing_device: "pen",
+            in_contact: true,
+            buttons: 0,
+            is_eraser: false,
+            in_proximity: true,
+        }
+    }
+
+    fn pressures(samples: [Option<TabletPayload>; 2]) -> Vec<f32> {
+        samples.into_iter().flatten().map(|s| s.pressure).collect()
+    }
+
+    #[test]
+    fn throttle_holds_the_newest_early_sample_until_it_is_due() {
+        let start = Instant::now();
+        let mut throttle = Throttle::new(Duration::from_millis(10));
+        assert_eq!(pressures(throttle.offer(sample(0.1), false, start)), [0.1]);
+        let early = start + Duration::from_millis(2);
+        assert!(pressures(throttle.offer(sample(0.2), false, early)).is_empty());
+        assert!(pressures(throttle.offer(sample(0.3), false, early)).is_empty());
+        assert!(throttle.flush(start + Duration::from_millis(5)).is_none());
+        let due = start + Duration::from_millis(10);
+        assert_eq!(throttle.flush(due).map(|s| s.pressure), Some(0.3));
+        assert!(throttle.flush(due + Duration::from_millis(10)).is_none());
+    }
+
+    #[test]
+    fn throttle_sends_the_held_sample_before_a_boundary() {
+        let start = Instant::now();
+        let mut throttle = Throttle::new(Duration::from_millis(10));
+        throttle.offer(sample(0.5), false, start);
+        let early = start + Duration::from_millis(2);
+        throttle.offer(sample(0.4), false, early);
+        assert_eq!(
+            pressures(throttle.offer(sample(0.0), true, early)),
+            [0.4, 0.0]
+        );
+        assert!(throttle.flush(start + Duration::from_millis(20)).is_none());
+    }
+
+    #[test]
+    fn throttle_drops_a_held_sample_once_a_newer_one_is_sent() {
+        let start = Instant::now();
+        let mut throttle = Throttle::new(Duration::from_millis(10));
+        throttle.offer(sample(0.1), false, start);
+        throttle.offer(sample(0.2), false, start + Duration::from_millis(2));
+        let due = start + Duration::from_millis(10);
+        assert_eq!(pressures(throttle.offer(sample(0.3), false, due)), [0.3]);
+        assert!(throttle.flush(due + Duration::from_millis(10)).is_none());
+    }
+
+    #[test]
+    fn sanitize_pressure_zeroes_non_finite_readings() {
+        assert_eq!(sanitize_pressure(f32::NAN), 0.0);
+        assert_eq!(sanitize_pressure(f32::INFINITY), 0.0);
+        assert_eq!(sanitize_pressure(f32::NEG_INFINITY), 0.0);
+    }
+
+    #[test]
+    fn sanitize_pressure_clamps_to_the_unit_range() {
+        assert_eq!(sanitize_pressure(-0.5), 0.0);
+        assert_eq!(sanitize_pressure(1.5), 1.0);
+        assert_eq!(sanitize_pressure(0.0), 0.0);
+        assert_eq!(sanitize_pressure(0.42), 0.42);
+        assert_eq!(sanitize_pressure(1.0), 1.0);
+    }
+
+    #[test]
+    fn sanitize_tilt_zeroes_non_finite_readings() {
+        assert_eq!(sanitize_tilt(f64::NAN), 0.0);
+        assert_eq!(sanitize_tilt(f64::INFINITY), 0.0);
+        assert_eq!(sanitize_tilt(f64::NEG_INFINITY), 0.0);
+    }
+
+    #[test]
+    fn sanitize_tilt_clamps_to_plus_or_minus_one() {
+        assert_eq!(sanitize_tilt(-2.0), -1.0);
+        assert_eq!(sanitize_tilt(2.0), 1.0);
+        assert_eq!(sanitize_tilt(-0.3), -0.3);
+        assert_eq!(sanitize_tilt(0.0), 0.0);
+        assert_eq!(sanitize_tilt(0.75), 0.75);
+    }
 }
@@ -0,0 +1,98 @@
+/// Crash watchdog for the sidecar.
+///
+/// Notices a ready sidecar whose process has exited and turns that into an
+/// `Error` status carrying the exit reason and the tail of its stderr, so
+/// the UI can show the actual Python traceback instead of just "stopped".
+/// A deliberate stop takes the child out of the state before killing it,
+/// so only exits nobody asked for are seen here, and the `Error` status
+/// leaves `start_sidecar` free to start a new one.
+///
+/// The check runs when the sidecar's stderr closes, which it does as the
+/// process exits, rather than on a timer. A slow fallback check covers a
+/// sidecar that exits while a child process of its own holds the pipe.
+use crate::commands::{describe_exit_status, set_status};
+use crate::events;
+use crate::stability::RunEnd;
+use crate::state::{AppState, LockExt, SidecarStatus};
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+const FALLBACK_INTERVAL: Duration = Duration::from_secs(10);
+/// How long after its stderr closes the process gets to be reaped.
+const REAP_TIMEOUT: Duration = Duration::from_secs(2);
+const REAP_POLL: Duration = Duration::from_millis(20);
+
+/// Serialized payload for the "sidecar-exited" event.
+#[derive(Clone, serde::Serialize)]
+struct ExitedPayload {
+    message: String,
+    /// `None` when the process was killed by a signal.
+    code: Option<i32>,
+}
+
+pub fn spawn_crash_watchdog(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let state = app.state::<AppState>();
+            let closed = tokio::select! {
+                _ = state.sidecar_output_closed.notified() => true,
+                _ = tokio::time::sleep(FALLBACK_INTERVAL) => false,
+            };
+            let exited = if closed {
+                wait_for_exit(&state).await
+            } else {
+                check_exited(&state)
+            };
+            let Some((message, code)) = exited else {
+                continue;
+            };
+            eprintln!("[sidecar] {}", message);
+            state.record_error("watchdog", &message);
+            let _ = set_status(&app, &state, SidecarStatus::Error(message.clone()));
+            events::emit_sidecar(&app, "sidecar-exited", ExitedPayload { message, code });
+        }
+    });
+}
+
+/// `check_exited` until the process has been reaped or `REAP_TIMEOUT`
+/// passes; a process can close its stderr and keep running.
+async fn wait_for_exit(state: &AppState) -> Option<(String, Option<i32>)> {
+    let deadline = tokio::time::Instant::now() + REAP_TIMEOUT;
+    loop {
+        if let Some(exited) = check_exited(state) {
+            return Some(exited);
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return None;
+        }
+        tokio::time::sleep(REAP_POLL).await;
+    }
+}
+
+/// If the ready sidecar's process has exited, clear it from the state and
+/// describe how it died, along with its exit code.
+fn check_exited(state: &AppState) -> Option<(String, Option<i32>)> {
+    let mut sidecar = state.sidecar.lock_or_recover();
+    if !matches!(sidecar.status, SidecarStatus::Ready) {
+        return None;
+    }
+    let status = sidecar.child.as_mut()?.try_wait().ok()??;
+    sidecar.child = None;
+    sidecar.ready = None;
+    state.end_run(RunEnd::Crashed);
+    let tail = sidecar
+        .stderr_tail
+        .take()
+        .map(|tail| {
+            let tail = tail.lock_or_recover();
+            tail.iter().cloned().collect::<Vec<_>>().join("\n")
+        })
+        .unwrap_or_default();
+    let how = describe_exit_status(status);
+    let message = if tail.is_empty() {
+        format!("Sidecar {} unexpectedly", how)
+    } else {
+        format!("Sidecar {} unexpectedly: {}", how, tail)
+    };
+    Some((message, status.code()))
+}
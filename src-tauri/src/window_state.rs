@@ -0,0 +1,140 @@
+/// Saving and restoring the main window's geometry.
+///
+/// Geometry is written to `Settings` shortly after the last move or resize
+/// instead of on every event of a drag. On restore it is only applied if it
+/// still lands on a connected monitor, so unplugging a display doesn't
+/// leave the window off-screen.
+use crate::config::{self, WindowGeometry};
+use crate::events::MAIN_WINDOW;
+use crate::state::{AppState, LockExt};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tauri::{AppHandle, Manager, PhysicalPosition, PhysicalRect, PhysicalSize, Window};
+
+/// Quiet period after the last move or resize before geometry is saved.
+const SAVE_DELAY: Duration = Duration::from_millis(500);
+
+/// How much of a saved window, per axis, must overlap a monitor's work area
+/// for the geometry to be reused.
+const MIN_VISIBLE_PX: i64 = 100;
+
+/// Bumped on every move or resize; a pending save only runs if no newer
+/// event has arrived since it was scheduled.
+static SAVE_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Apply the saved geometry to the main window. Called once during setup.
+pub fn restore(app: &AppHandle) {
+    let Some(window) = app.get_webview_window(MAIN_WINDOW) else {
+        return;
+    };
+    let saved = app
+        .state::<AppState>()
+        .settings
+        .lock_or_recover()
+        .window
+        .clone();
+    let Some(saved) = saved else {
+        return;
+    };
+    let Ok(monitors) = window.available_monitors() else {
+        return;
+    };
+    // No match means the monitor it was on is gone; keep the default
+    // centered placement.
+    let Some(area) = monitors
+        .iter()
+        .map(|monitor| *monitor.work_area())
+        .find(|area| overlaps(&saved, area))
+    else {
+        return;
+    };
+
+    // Shrink and pull the window fully onto the monitor, which may be
+    // smaller than the one it was saved on.
+    let width = saved.width.min(area.size.width);
+    let height = saved.height.min(area.size.height);
+    let x = saved.x.clamp(
+        area.position.x,
+        area.position.x + (area.size.width - width) as i32,
+    );
+    let y = saved.y.clamp(
+        area.position.y,
+        area.position.y + (area.size.height - height) as i32,
+    );
+    let _ = window.set_size(PhysicalSize::new(width, height));
+    let _ = window.set_position(PhysicalPosition::new(x, y));
+    if saved.maximized {
+        let _ = window.maximize();
+    }
+}
+
+fn overlaps(geometry: &WindowGeometry, area: &PhysicalRect<i32, u32>) -> bool {
+    let overlap = |start: i32, len: u32, area_start: i32, area_len: u32| {
+        let end = (start as i64 + len as i64).min(area_start as i64 + area_len as i64);
+        end - (start as i64).max(area_start as i64)
+    };
+    overlap(geometry.x, geometry.width, area.position.x, area.size.width) >= MIN_VISIBLE_PX
+        && overlap(
+            geometry.y,
+            geometry.height,
+            area.position.y,
+            area.size.height,
+        ) >= MIN_VISIBLE_PX
+}
+
+/// Save the main window's geometry once it stops moving.
+pub fn schedule_save(window: &Window) {
+    if window.label() != MAIN_WINDOW {
+        return;
+    }
+    let generation = SAVE_GENERATION.fetch_add(1, Ordering::Relaxed) + 1;
+    let app = window.app_handle().clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(SAVE_DELAY).await;
+        if SAVE_GENERATION.load(Ordering::Relaxed) == generation {
+            save(&app);
+        }
+    });
+}
+
+fn save(app: &AppHandle) {
+    let Some(window) = app.get_webview_window(MAIN_WINDOW) else {
+        return;
+    };
+    // A minimized window's geometry is meaningless; keep the last good one.
+    if window.is_minimized().unwrap_or(false) {
+        return;
+    }
+    // Query the window before taking the settings lock: window calls hop to
+    // the main thread, which may itself be waiting on that lock.
+    let maximized = window.is_maximized().unwrap_or(false);
+    let current = match (window.outer_position(), window.inner_size()) {
+        (Ok(position), Ok(size)) => WindowGeometry {
+            x: position.x,
+            y: position.y,
+            width: size.width,
+            height: size.height,
+            maximized,
+        },
+        _ => return,
+    };
+
+    let state = app.state::<AppState>();
+    let mut settings = state.settings.lock_or_recover();
+    let geometry = match &settings.window {
+        // Remember the size to restore to when un-maximizing, not the
+        // maximized one.
+        Some(previous) if maximized => WindowGeometry {
+            maximized: true,
+            ..previous.clone()
+        },
+        _ => current,
+    };
+    if settings.window.as_ref() == Some(&geometry) {
+        return;
+    }
+    settings.window = Some(geometry);
+    if let Err(e) = config::save(app, &settings) {
+        eprintln!("[window] failed to save window geometry: {}", e);
+    }
+}
@@ -0,0 +1,210 @@
+//! `SidecarClient` against the in-process mock sidecar. Run with
+//! `cargo test --features test-support`.
+use pictaflux_lib::test_support::{
+    ClientConfig, ClientError, MockResponse, MockSidecar, SidecarClient,
+};
+use std::time::Duration;
+
+fn client(mock: &MockSidecar) -> SidecarClient {
+    SidecarClient::new(mock.addr(), ClientConfig::default())
+}
+
+/// A client that retries `retries` times without waiting long in between.
+fn retrying_client(mock: &MockSidecar, retries: u32) -> SidecarClient {
+    SidecarClient::new(
+        mock.addr(),
+        ClientConfig {
+            retries,
+            retry_backoff_ms: 1,
+            ..ClientConfig::default()
+        },
+    )
+}
+
+#[tokio::test]
+async fn probe_reaches_a_healthy_sidecar() {
+    let mock = MockSidecar::start().await.unwrap();
+    assert!(client(&mock).probe(Duration::from_secs(1)).await);
+    assert_eq!(mock.hits("GET", "health"), 1);
+}
+
+#[tokio::test]
+async fn probe_counts_a_sidecar_without_health_as_reachable() {
+    let mock = MockSidecar::start().await.unwrap();
+    mock.respond("GET", "health", MockResponse::status(404));
+    assert!(client(&mock).probe(Duration::from_secs(1)).await);
+}
+
+#[tokio::test]
+async fn probe_fails_for_an_unhealthy_sidecar() {
+    let mock = MockSidecar::start().await.unwrap();
+    mock.respond("GET", "health", MockResponse::status(500));
+    assert!(!client(&mock).probe(Duration::from_secs(1)).await);
+}
+
+#[tokio::test]
+async fn probe_fails_when_nothing_is_listening() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    drop(listener);
+    let client = SidecarClient::new(
+        addr,
+        ClientConfig {
+            retries: 0,
+            ..ClientConfig::default()
+        },
+    );
+    assert!(!client.probe(Duration::from_secs(1)).await);
+}
+
+#[tokio::test]
+async fn models_are_listed() {
+    let mock = MockSidecar::start().await.unwrap();
+    let models = client(&mock).models().await.unwrap();
+    assert_eq!(models.len(), 1);
+    assert_eq!(models[0].id, "sdxs");
+}
+
+#[tokio::test]
+async fn requests_carry_the_api_version() {
+    let mock = MockSidecar::start().await.unwrap();
+    client(&mock).models().await.unwrap();
+    let request = &mock.requests()[0];
+    assert_eq!(
+        request.headers.get("x-pictaflux-api").map(String::as_str),
+        Some("1")
+    );
+}
+
+#[tokio::test]
+async fn a_different_api_version_is_a_mismatch() {
+    let mock = MockSidecar::start().await.unwrap();
+    mock.respond(
+        "GET",
+        "models",
+        MockResponse::json(200, serde_json::json!([])).with_api_version(Some("2")),
+    );
+    match client(&mock).models().await {
+        Err(ClientError::VersionMismatch { server }) => assert_eq!(server, "2"),
+        other => panic!("expected a version mismatch, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn a_sidecar_without_the_version_header_is_accepted() {
+    let mock = MockSidecar::start().await.unwrap();
+    mock.respond(
+        "GET",
+        "models",
+        MockResponse::json(200, serde_json::json!([])).with_api_version(None),
+    );
+    assert!(client(&mock).models().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn missing_endpoints_are_unsupported() {
+    let mock = MockSidecar::start().await.unwrap();
+    mock.respond("POST", "flush_cache", MockResponse::status(404));
+    mock.respond("POST", "model_cache", MockResponse::status(501));
+    assert!(matches!(
+        client(&mock).flush_cache().await,
+        Err(ClientError::Unsupported("flush_cache"))
+    ));
+    assert!(matches!(
+        client(&mock).set_model_cache("/tmp/hf").await,
+        Err(ClientError::Unsupported("model_cache"))
+    ));
+    // Not worth retrying: the endpoint won't appear on its own.
+    assert_eq!(mock.hits("POST", "flush_cache"), 1);
+}
+
+#[tokio::test]
+async fn slow_replies_time_out() {
+    let mock = MockSidecar::start().await.unwrap();
+    mock.respond(
+        "GET",
+        "health",
+        MockResponse::status(200).with_delay(Duration::from_secs(2)),
+    );
+    match retrying_client(&mock, 0)
+        .health(Duration::from_millis(200))
+        .await
+    {
+        Err(ClientError::Unreachable(e)) => assert!(e.is_timeout(), "{}", e),
+        other => panic!("expected a timeout, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn timeouts_are_retried() {
+    let mock = MockSidecar::start().await.unwrap();
+    mock.respond(
+        "GET",
+        "health",
+        MockResponse::status(200).with_delay(Duration::from_secs(2)),
+    );
+    let result = retrying_client(&mock, 1)
+        .health(Duration::from_millis(200))
+        .await;
+    assert!(matches!(result, Err(ClientError::Unreachable(_))));
+    assert_eq!(mock.hits("GET", "health"), 2);
+}
+
+#[tokio::test]
+async fn unavailable_is_retried_up_to_the_limit() {
+    let mock = MockSidecar::start().await.unwrap();
+    mock.respond("GET", "models", MockResponse::status(503));
+    let result = retrying_client(&mock, 2).models().await;
+    assert!(matches!(
+        result,
+        Err(ClientError::Http { status, .. }) if status.as_u16() == 503
+    ));
+    assert_eq!(mock.hits("GET", "models"), 3);
+}
+
+#[tokio::test]
+async fn server_errors_are_not_retried() {
+    let mock = MockSidecar::start().await.unwrap();
+    mock.respond("GET", "models", MockResponse::status(500));
+    assert!(retrying_client(&mock, 2).models().await.is_err());
+    assert_eq!(mock.hits("GET", "models"), 1);
+}
+
+#[tokio::test]
+async fn model_switches_are_not_retried() {
+    let mock = MockSidecar::start().await.unwrap();
+    mock.respond("POST", "model", MockResponse::status(503));
+    assert!(retrying_client(&mock, 2)
+        .switch_model("sdxs")
+        .await
+        .is_err());
+    assert_eq!(mock.hits("POST", "model"), 1);
+}
+
+#[tokio::test]
+async fn handshake_succeeds_with_the_api_version() {
+    let mock = MockSidecar::start().await.unwrap();
+    assert!(client(&mock).handshake(Duration::from_secs(1)).await);
+}
+
+#[tokio::test]
+async fn handshake_fails_without_the_api_version() {
+    let mock = MockSidecar::start().await.unwrap();
+    mock.respond(
+        "GET",
+        "health",
+        MockResponse::status(200).with_api_version(None),
+    );
+    assert!(!client(&mock).handshake(Duration::from_secs(1)).await);
+}
+
+#[tokio::test]
+async fn handshake_fails_for_another_api_version() {
+    let mock = MockSidecar::start().await.unwrap();
+    mock.respond(
+        "GET",
+        "health",
+        MockResponse::status(200).with_api_version(Some("2")),
+    );
+    assert!(!client(&mock).handshake(Duration::from_secs(1)).await);
+}
@@ -1,3 +1,6 @@
+import { listen, type UnlistenFn } from "@tauri-apps/api/event";
+import { captureLoopFrame, getLowPower, type LowPowerConfig } from "./tauri";
+
 export type DiffusionState =
   | "disconnected"
   | "connecting"
@@ -6,6 +9,8 @@ export type DiffusionState =
 
 export interface DiffusionBridgeOptions {
   port: number;
+  // Defaults to loopback, which also reaches a sidecar bound to 0.0.0.0.
+  host?: string;
   onFrame: (imageUrl: string) => void;
   onStateChange: (state: DiffusionState) => void;
   onFpsUpdate: (fps: number) => void;
@@ -21,6 +26,16 @@ export class DiffusionBridge {
   private frameCount = 0;
   private fpsStartTime = 0;
   private pendingFrame = false;
+  private strength = 0.5;
+  // [frame, strength] pairs sorted by frame; null when strength is static.
+  private strengthSchedule: [number, number][] | null = null;
+  private loopFrame = 0;
+  private sentStrength: number | null = null;
+  private capturing = false;
+  // Low-power pacing; 0 sends the next frame as soon as one returns.
+  private minFrameIntervalMs = 0;
+  private lastSendTime = 0;
+  private unlistenLowPower: UnlistenFn | null = null;
 
   constructor(options: DiffusionBridgeOptions) {
     this.options = options;
@@ -28,9 +43,11 @@ export class DiffusionBridge {
 
   connect(): void {
     this.options.onStateChange("connecting");
-    const url = `ws://127.0.0.1:${this.options.port}`;
+    const host = this.options.host ?? "127.0.0.1";
+    const url = `ws://${host.includes(":") ? `[${host}]` : host}:${this.options.port}`;
     this.ws = new WebSocket(url);
     this.ws.binaryType = "arraybuffer";
+    this.followLowPower();
 
     this.ws.onopen = () => {
       this.options.onStateChange("connected");
@@ -38,6 +55,8 @@ export class DiffusionBridge {
       this.frameCount = 0;
       this.fpsStartTime = performance.now();
       this.pendingFrame = false;
+      this.sentStrength = null;
+      this.sendMinFrameInterval();
       this.sendNextFrame();
     };
 
@@ -61,6 +80,8 @@ export class DiffusionBridge {
 
   disconnect(): void {
     this.running = false;
+    this.unlistenLowPower?.();
+    this.unlistenLowPower = null;
     if (this.ws) {
       this.ws.close();
       this.ws = null;
@@ -87,9 +108,100 @@ export class DiffusionBridge {
     }
   }
 
+  setFeedbackMode(value: "alpha" | "additive" | "difference" | "momentum"): void {
+    if (this.ws && this.ws.readyState === WebSocket.OPEN) {
+      this.ws.send(JSON.stringify({ type: "set_feedback_mode", value }));
+    }
+  }
+
+  // Space frames at least `ms` apart, from the backend's low-power config.
+  // The sidecar is told too, so it paces any other client the same way.
+  setMinFrameInterval(ms: number): void {
+    this.minFrameIntervalMs = Math.max(0, ms);
+    this.sendMinFrameInterval();
+  }
+
+  private sendMinFrameInterval(): void {
+    if (this.ws && this.ws.readyState === WebSocket.OPEN) {
+      this.ws.send(
+        JSON.stringify({ type: "set_min_frame_interval", value: this.minFrameIntervalMs }),
+      );
+    }
+  }
+
+  // Follow low-power mode for as long as the bridge is connected.
+  private followLowPower(): void {
+    const apply = (config: LowPowerConfig) =>
+      this.setMinFrameInterval(config.min_frame_interval_ms);
+    getLowPower()
+      .then(apply)
+      .catch((e) => this.options.onError(String(e)));
+    listen<LowPowerConfig>("low-power-changed", (event) => apply(event.payload))
+      .then((unlisten) => {
+        this.unlistenLowPower?.();
+        this.unlistenLowPower = null;
+        if (this.ws) this.unlistenLowPower = unlisten;
+        else unlisten();
+      })
+      .catch((e) => this.options.onError(String(e)));
+  }
+
+  // Hand each result frame to the backend while a loop capture is active.
+  setCapturing(enabled: boolean): void {
+    this.capturing = enabled;
+  }
+
   setStrength(value: number): void {
+    this.strength = value;
+    if (this.strengthSchedule) return;
+    this.sendStrength(value);
+  }
+
+  /**
+   * Vary strength across frames of the live loop. Strength is interpolated
+   * linearly between keyframes and the schedule repeats after the last one,
+   * so it works as a looping animation. Pass null to return to the static
+   * strength.
+   */
+  setStrengthSchedule(keyframes: [number, number][] | null): void {
+    if (keyframes === null || keyframes.length === 0) {
+      this.strengthSchedule = null;
+      this.sendStrength(this.strength);
+      return;
+    }
+    for (const [frame, strength] of keyframes) {
+      if (!Number.isInteger(frame) || frame < 0) {
+        throw new Error(`Keyframe frame must be a non-negative integer, got ${frame}`);
+      }
+      if (!(strength >= 0 && strength <= 1)) {
+        throw new Error(`Keyframe strength must be between 0 and 1, got ${strength}`);
+      }
+    }
+    this.strengthSchedule = [...keyframes].sort((a, b) => a[0] - b[0]);
+    this.loopFrame = 0;
+  }
+
+  private scheduledStrength(schedule: [number, number][]): number {
+    const length = schedule[schedule.length - 1][0] + 1;
+    const frame = this.loopFrame % length;
+    let prev = schedule[0];
+    if (frame <= prev[0]) return prev[1];
+    for (const next of schedule) {
+      if (frame <= next[0]) {
+        const t = (frame - prev[0]) / (next[0] - prev[0]);
+        return prev[1] + t * (next[1] - prev[1]);
+      }
+      prev = next;
+    }
+    return prev[1];
+  }
+
+  private sendStrength(value: number): void {
+    // Skip no-op updates so a flat stretch of the schedule costs nothing.
+    if (this.sentStrength !== null && Math.abs(this.sentStrength - value) < 1e-3) return;
     if (this.ws && this.ws.readyState === WebSocket.OPEN) {
       this.ws.send(JSON.stringify({ type: "set_strength", value }));
+      this.sentStrength = value;
     }
   }
 
@@ -99,6 +211,13 @@ export class DiffusionBridge {
     }
   }
 
+  // Locked, every frame uses the current seed; unlocked, it advances per frame.
+  setSeedLock(locked: boolean): void {
+    if (this.ws && this.ws.readyState === WebSocket.OPEN) {
+      this.ws.send(JSON.stringify({ type: "set_seed_lock", value: locked }));
+    }
+  }
+
   setSeed(value: number): void {
     if (this.ws && this.ws.readyState === WebSocket.OPEN) {
       this.ws.send(JSON.stringify({ type: "set_seed", value: Math.floor(value) }));
@@ -130,7 +249,12 @@ export class DiffusionBridge {
     if (!blob || !this.ws || this.ws.readyState !== WebSocket.OPEN) return;
 
     const buffer = await blob.arrayBuffer();
+    if (this.strengthSchedule) {
+      this.sendStrength(this.scheduledStrength(this.strengthSchedule));
+      this.loopFrame++;
+    }
     this.pendingFrame = true;
+    this.lastSendTime = performance.now();
     this.ws.send(buffer);
   }
 
@@ -145,6 +269,10 @@ export class DiffusionBridge {
     this.lastObjectUrl = URL.createObjectURL(blob);
     this.options.onFrame(this.lastObjectUrl);
 
+    if (this.capturing) {
+      captureLoopFrame(data).catch((e) => this.options.onError(String(e)));
+    }
+
     this.frameCount++;
     const elapsed = (performance.now() - this.fpsStartTime) / 1000;
     if (elapsed >= 1.0) {
@@ -153,6 +281,11 @@ export class DiffusionBridge {
       this.fpsStartTime = performance.now();
     }
 
-    requestAnimationFrame(() => this.sendNextFrame());
+    const wait = this.minFrameIntervalMs - (performance.now() - this.lastSendTime);
+    if (wait > 0) {
+      setTimeout(() => requestAnimationFrame(() => this.sendNextFrame()), wait);
+    } else {
+      requestAnimationFrame(() => this.sendNextFrame());
+    }
   }
 }
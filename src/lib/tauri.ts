@@ -1,19 +1,67 @@
 import { invoke } from "@tauri-apps/api/core";
 
+export interface ProcessResult {
+  image_base64: string;
+  seed: number;
+  inference_ms: number;
+  model: string;
+  // Dispatch order; a result older than one already shown is stale.
+  frame_id: number;
+}
+
+export async function getFrameGeneration(): Promise<number> {
+  return await invoke<number>("get_frame_generation");
+}
+
 export async function processCanvas(
   imageBase64: string,
   prompt: string,
-): Promise<string> {
-  return await invoke<string>("process_canvas", { imageBase64, prompt });
+  seed?: number,
+  // Renders just this frame with a different prompt; the session keeps `prompt`.
+  promptOverride?: string,
+  regions?: PromptRegion[],
+): Promise<ProcessResult> {
+  return await invoke<ProcessResult>("process_canvas", {
+    imageBase64,
+    prompt,
+    promptOverride,
+    regions,
+    seed,
+  });
+}
+
+// A prompt for part of the canvas. The mask is a canvas-sized image whose
+// bright pixels mark the region; later regions win where masks overlap.
+export interface PromptRegion {
+  mask_base64: string;
+  prompt: string;
 }
 
 export interface SidecarStartResult {
   port: number;
+  // From a validate-only start: the process holding the port, which a real
+  // start would kill first.
+  port_cleanup?: string;
+}
+
+export interface SidecarStatus {
+  state: "stopped" | "loading" | "ready" | "restarting" | "error";
+  // Set when `state` is "error".
+  message: string | null;
+  // Set when `state` is "restarting".
+  attempt: number | null;
 }
 
 export interface SidecarStatusResponse {
-  status: "stopped" | "loading" | "ready" | { error: string };
+  status: SidecarStatus;
+  // Address the sidecar is bound to, e.g. "127.0.0.1" or "0.0.0.0".
+  host: string;
   port: number;
+  model: string | null;
+  safe_mode: boolean;
+  busy: boolean;
+  // Whether a health check got an answer; null unless probed.
+  reachable: boolean | null;
 }
 
 export async function startSidecar(
@@ -23,6 +71,9 @@ export async function startSidecar(
   strength: number = 0.5,
   model: string = "sdxs",
   renderSize: number = 512,
+  bindHost?: string,
+  // Interpreter to run the sidecar with; defaults to the configured one.
+  pythonPath?: string,
 ): Promise<SidecarStartResult> {
   return await invoke<SidecarStartResult>("start_sidecar", {
     port,
@@ -31,13 +82,108 @@ export async function startSidecar(
     strength,
     model,
     renderSize,
+    bindHost,
+    pythonPath,
   });
 }
 
+// Saved settings the UI restores on load; get_settings returns the rest of
+// the settings too (see config.rs).
+export interface SessionSettings {
+  port: number;
+  last_prompt: string;
+  feedback: number;
+  strength: number;
+}
+
+export async function getSettings(): Promise<SessionSettings> {
+  return await invoke<SessionSettings>("get_settings");
+}
+
 export async function stopSidecar(): Promise<void> {
   return await invoke<void>("stop_sidecar");
 }
 
-export async function getSidecarStatus(): Promise<SidecarStatusResponse> {
-  return await invoke<SidecarStatusResponse>("get_sidecar_status");
+// Relaunches with the last start's settings and the live prompt/params.
+export async function restartSidecar(): Promise<SidecarStartResult> {
+  return await invoke<SidecarStartResult>("restart_sidecar");
+}
+
+export async function getPort(): Promise<number> {
+  return await invoke<number>("get_port");
+}
+
+// With `probe`, also health-checks the sidecar and fills in `reachable`.
+export async function getSidecarStatus(
+  probe: boolean = false,
+): Promise<SidecarStatusResponse> {
+  return await invoke<SidecarStatusResponse>("get_sidecar_status", { probe });
+}
+
+export interface LoopCaptureSummary {
+  frames: number;
+  output_dir: string;
+  video: string | null;
+}
+
+export async function startLoopCapture(outputDir: string): Promise<void> {
+  return await invoke<void>("start_loop_capture", { outputDir });
+}
+
+export async function captureLoopFrame(data: ArrayBuffer): Promise<number> {
+  return await invoke<number>("capture_loop_frame", {
+    data: Array.from(new Uint8Array(data)),
+  });
+}
+
+export async function stopLoopCapture(
+  makeVideo: boolean = false,
+  fps?: number,
+): Promise<LoopCaptureSummary> {
+  return await invoke<LoopCaptureSummary>("stop_loop_capture", {
+    makeVideo,
+    fps,
+  });
+}
+
+export interface LowPowerConfig {
+  enabled: boolean;
+  // Pass to DiffusionBridge.setMinFrameInterval.
+  min_frame_interval_ms: number;
+  max_render_size: number | null;
+}
+
+export async function setLowPower(enabled: boolean): Promise<LowPowerConfig> {
+  return await invoke<LowPowerConfig>("set_low_power", { enabled });
+}
+
+export async function getLowPower(): Promise<LowPowerConfig> {
+  return await invoke<LowPowerConfig>("get_low_power");
+}
+
+export type FeedbackMode = "alpha" | "additive" | "difference" | "momentum";
+
+// Switches a running sidecar live; later starts use the same mode.
+export async function setFeedbackMode(mode: FeedbackMode): Promise<void> {
+  return await invoke<void>("set_feedback_mode", { mode });
+}
+
+// Keyframes are [frame, strength] pairs in ascending frame order; pass the
+// same ones to DiffusionBridge.setStrengthSchedule, which drives the live
+// loop. null clears the schedule and restores the static strength.
+export async function setStrengthSchedule(
+  keyframes: [number, number][] | null,
+): Promise<void> {
+  return await invoke<void>("set_strength_schedule", { keyframes });
+}
+
+export async function getStrengthSchedule(): Promise<[number, number][] | null> {
+  return await invoke<[number, number][] | null>("get_strength_schedule");
+}
+
+// Also switches a running sidecar's live loop. Returns the seed held while
+// locked; pass it to DiffusionBridge.setSeed to lock the live loop to the
+// same one.
+export async function setSeedLock(locked: boolean): Promise<number> {
+  return await invoke<number>("set_seed_lock", { locked });
 }